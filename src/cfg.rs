@@ -0,0 +1,165 @@
+use std::cmp;
+use rustc::session::Session;
+use syntax::ast::{self, Attribute, CrateConfig};
+use syntax::attr;
+use syntax::codemap::{BytePos, Span, mk_sp};
+use syntax::print::pprust;
+use syntax::visit::{self, Visitor};
+
+
+/// Code removed by cfg stripping and the predicate which turned it off.
+pub struct StrippedSpan {
+    pub span: Span,
+    pub predicate: String
+}
+
+
+/// Finds code which `phase_2_configure_and_expand` is going to strip from the parsed crate.
+pub fn collect_stripped(sess: &Session, config: &CrateConfig, krate: &ast::Crate) -> Vec<StrippedSpan> {
+    let mut collector = StrippedCollector { sess: sess, config: config, stripped: Vec::new() };
+    visit::walk_crate(&mut collector, krate);
+    collector.stripped
+}
+
+
+struct StrippedCollector<'a> {
+    sess: &'a Session,
+    config: &'a CrateConfig,
+    stripped: Vec<StrippedSpan>
+}
+
+
+impl<'a> StrippedCollector<'a> {
+    fn failed_predicate(&self, attrs: &[Attribute]) -> Option<String> {
+        attrs.iter().filter_map(|attr| self.failed_cfg(&attr.node.value)).next()
+    }
+
+    /// Predicate of `cfg(predicate)` if it is false, e.g. `all(unix, not(test))`.
+    /// `cfg_attr(condition, cfg(predicate))` is checked as `cfg(predicate)` when the condition holds.
+    fn failed_cfg(&self, meta: &ast::MetaItem) -> Option<String> {
+        let matches = |predicate: &ast::MetaItem| attr::cfg_matches(self.sess.diagnostic(), self.config, predicate);
+        match meta.node {
+            // rustc reports cfg with several predicates and keeps the code
+            ast::MetaList(ref name, ref items) if &name[..] == "cfg" && items.len() == 1 =>
+                if matches(&items[0]) { None } else { Some(pprust::meta_item_to_string(&items[0])) },
+            ast::MetaList(ref name, ref items) if &name[..] == "cfg_attr" && items.len() == 2 =>
+                if matches(&items[0]) { self.failed_cfg(&items[1]) } else { None },
+            _ => None
+        }
+    }
+
+    /// Records the node with its attributes if it is disabled.
+    fn is_active(&mut self, attrs: &[Attribute], span: Span) -> bool {
+        match self.failed_predicate(attrs) {
+            Some(predicate) => {
+                let lo = attrs.iter().map(|attr| attr.span.lo.0).fold(span.lo.0, cmp::min);
+                self.stripped.push(StrippedSpan { span: mk_sp(BytePos(lo), span.hi), predicate: predicate });
+                false
+            },
+            None => true
+        }
+    }
+}
+
+
+impl<'a, 'v> Visitor<'v> for StrippedCollector<'a> {
+    fn visit_item(&mut self, item: &'v ast::Item) {
+        if self.is_active(&item.attrs, item.span) {
+            return visit::walk_item(self, item);
+        }
+        // contents of `mod foo;` live in another file, which is disabled as a whole
+        if let ast::ItemMod(ref module) = item.node {
+            if module.inner.lo.0 < item.span.lo.0 || module.inner.hi.0 > item.span.hi.0 {
+                let predicate = self.stripped.last().map_or(String::new(), |x| x.predicate.clone());
+                self.stripped.push(StrippedSpan { span: module.inner, predicate: predicate });
+            }
+        }
+    }
+
+    fn visit_foreign_item(&mut self, item: &'v ast::ForeignItem) {
+        if self.is_active(&item.attrs, item.span) {
+            visit::walk_foreign_item(self, item);
+        }
+    }
+
+    fn visit_trait_item(&mut self, item: &'v ast::TraitItem) {
+        if self.is_active(&item.attrs, item.span) {
+            visit::walk_trait_item(self, item);
+        }
+    }
+
+    fn visit_impl_item(&mut self, item: &'v ast::ImplItem) {
+        if self.is_active(&item.attrs, item.span) {
+            visit::walk_impl_item(self, item);
+        }
+    }
+
+    fn visit_struct_field(&mut self, field: &'v ast::StructField) {
+        if self.is_active(&field.node.attrs, field.span) {
+            visit::walk_struct_field(self, field);
+        }
+    }
+
+    fn visit_variant(&mut self, variant: &'v ast::Variant, generics: &'v ast::Generics) {
+        if self.is_active(&variant.node.attrs, variant.span) {
+            visit::walk_variant(self, variant, generics);
+        }
+    }
+
+    fn visit_arm(&mut self, arm: &'v ast::Arm) {
+        let lo = arm.pats.first().map_or(arm.body.span.lo, |pat| pat.span.lo);
+        if self.is_active(&arm.attrs, mk_sp(lo, arm.body.span.hi)) {
+            visit::walk_arm(self, arm);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use compiler_api::{CrateType, build_session, parse_and_expand};
+    use explorer::CrateJob;
+    use diagnostics::Diagnostics;
+
+    const SOURCE: &'static str = "\
+#[cfg(all(foo, not(bar)))]
+fn active() {}
+
+#[cfg(any(bar, all(foo, baz)))]
+fn inactive() {}
+
+#[cfg_attr(foo, cfg(not(foo)))]
+fn inactive_by_attr() {}
+
+#[cfg_attr(bar, cfg(bar))]
+fn active_by_attr() {}
+
+struct Point {
+    #[cfg(not(any(foo, bar)))]
+    x: i32,
+    y: i32
+}
+
+fn main() {}
+";
+
+    #[test]
+    fn test_collect_stripped() {
+        let mut job = CrateJob::fixture("cfg", CrateType::Executable, &[("main.rs", SOURCE)]);
+        job.compiler_options.args = vec!["--cfg".to_string(), "foo".to_string()];
+        let sess = build_session(job.source_path.clone(), job.crate_type, &job.compiler_options, &Diagnostics::new())
+            .unwrap();
+        let (_, _, stripped) = parse_and_expand(&sess, &job.source_path).unwrap();
+
+        let stripped = stripped.iter()
+            .map(|x| (x.predicate.clone(), sess.codemap().span_to_snippet(x.span).unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(stripped, vec![
+            ("any(bar, all(foo, baz))".to_string(), "#[cfg(any(bar, all(foo, baz)))]\nfn inactive() {}".to_string()),
+            ("not(foo)".to_string(), "#[cfg_attr(foo, cfg(not(foo)))]\nfn inactive_by_attr() {}".to_string()),
+            ("not(any(foo, bar))".to_string(), "#[cfg(not(any(foo, bar)))]\n    x: i32".to_string())
+        ]);
+        fs::remove_dir_all(&job.input).unwrap();
+    }
+}
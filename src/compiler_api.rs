@@ -4,16 +4,22 @@ pub use syntax::ast::Crate;
 pub use syntax::ast_map::{Forest, Map};
 pub use rustc_driver::driver::assign_node_ids_and_map;
 pub use rustc::middle::ty::{CrateAnalysis, CtxtArenas};
+pub use rustc::middle::def::DefMap;
 
-use rustc::session::config::{CrateType, Input, build_configuration, self};
+use rustc::session::config::{Input, build_configuration, self};
 use rustc::session::Session;
 
 use rustc_driver;
 use rustc_resolve;
 use getopts;
+use manifest::{CrateTarget, TargetKind};
+use cfg::{StrippedSpan, collect_stripped};
+use diagnostics::Diagnostics;
+use errors::{Error, Result};
 
 
-pub fn parse_and_expand(sess: &Session, source_path: &Path) -> Option<(String, Crate)> {
+/// Returns crate name, expanded crate and code stripped by cfg attributes.
+pub fn parse_and_expand(sess: &Session, source_path: &Path) -> Option<(String, Crate, Vec<StrippedSpan>)> {
     use rustc_trans::back::link;
     use rustc_driver::driver::{
         phase_1_parse_input,
@@ -23,18 +29,29 @@ pub fn parse_and_expand(sess: &Session, source_path: &Path) -> Option<(String, C
     let cfg = build_configuration(&sess);
 
     let input = &Input::File(source_path.into());
-    println!("phase_1_parse_input");
+    log_debug!("phase_1_parse_input");
     let krate = phase_1_parse_input(sess, cfg, input);
+    let stripped = collect_stripped(sess, &krate.config, &krate);
 
-    println!("find_crate_name");
+    log_debug!("find_crate_name");
     let id = link::find_crate_name(
         Some(sess), &krate.attrs, input
     );
 
-    println!("phase_2_configure_and_expand");
+    log_debug!("phase_2_configure_and_expand");
     phase_2_configure_and_expand(
         sess, krate, &id[..], None
-    ).map(|krate| (id, krate))
+    ).map(|krate| (id, krate, stripped))
+}
+
+
+/// Resolves the paths of the crate, as `analyze` does before the type checking.
+/// Lints found on the way are dropped, since `analyze` finds them again.
+pub fn resolve<'ast>(sess: &Session, ast_map: &Map<'ast>) -> DefMap {
+    let lints = sess.lints.borrow().clone();
+    let crate_map = rustc_resolve::resolve_crate(sess, ast_map, rustc_resolve::MakeGlobMap::No);
+    *sess.lints.borrow_mut() = lints;
+    crate_map.def_map
 }
 
 
@@ -51,60 +68,104 @@ pub fn get_main_file_path(crate_path: &Path) -> Option<(PathBuf, CrateType)> {
     use std::fs::PathExt;
 
     vec![
-        (crate_path.join("main.rs"), CrateType::CrateTypeExecutable),
-        (crate_path.join("lib.rs"), CrateType::CrateTypeDylib)
+        (crate_path.join("main.rs"), CrateType::Executable),
+        (crate_path.join("lib.rs"), CrateType::Rlib)
     ].into_iter().find(|&(ref path, _)| path.is_file())
 }
 
 
-pub fn build_session(input_file_path: PathBuf, crate_type: CrateType) -> Session {
-    use rustc::session;
-    use rustc::session::config::{basic_options, UnstableFeatures, self};
-    use rustc::session::config::DebugInfoLevel;
-    use rustc_driver::CompilerCalls;
+/// Libraries are analyzed as the first of their crate types known to rustc, as rlibs by default.
+pub fn target_crate_type(target: &CrateTarget) -> CrateType {
+    if target.kind != TargetKind::Lib {
+        return CrateType::Executable;
+    }
+    target.crate_types.iter()
+        .filter_map(|name| match &name[..] {
+            "lib" | "rlib" => Some(CrateType::Rlib),
+            // proc macros are loaded by the compiler as dynamic libraries
+            "dylib" | "cdylib" | "proc-macro" => Some(CrateType::Dylib),
+            "staticlib" => Some(CrateType::Staticlib),
+            _ => None
+        })
+        .next()
+        .unwrap_or(CrateType::Rlib)
+}
 
-    let descriptions = rustc_driver::diagnostics_registry();
 
-    //let mut sopts = basic_options();
-    //sopts.unstable_features = UnstableFeatures::Default;
-    //sopts.crate_types.push(crate_type);
-    //sopts.maybe_sysroot = Some(PathBuf::from("/usr/local"));
-    //sopts.debug_assertions = true;
-    //sopts.debuginfo = DebugInfoLevel::FullDebugInfo;
+/// Type of the crate as passed to rustc with `--crate-type`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CrateType {
+    Executable,
+    Rlib,
+    Dylib,
+    Staticlib
+}
+
+
+/// Compiler settings besides the crate root and its type.
+#[derive(Clone, Debug, Default)]
+pub struct CompilerOptions {
+    pub sysroot: Option<PathBuf>,
+    pub args: Vec<String> // passed to rustc as is, e.g. `--cfg`, `-L`, `--extern`
+}
 
 
-    let mut callbacks = rustc_driver::RustcDefaultCalls;
+/// Diagnostics are printed to stderr and collected into `diagnostics`.
+pub fn build_session(
+    input_file_path: PathBuf, crate_type: CrateType, options: &CompilerOptions, diagnostics: &Diagnostics
+) -> Result<Session> {
+    use rustc::session;
+    use syntax::codemap::CodeMap;
+    use syntax::diagnostic::{self, EmitterWriter};
+
+    let descriptions = rustc_driver::diagnostics_registry();
 
-    let command = format!("rustc --sysroot /usr/local --crate-type lib {}", input_file_path.to_str().unwrap());
-    let args: Vec<_> = command.split(" ").map(|x| x.to_string()).collect();
+    let mut args = vec!["rustc".to_string()];
+    if let Some(ref sysroot) = options.sysroot {
+        args.push("--sysroot".to_string());
+        args.push(sysroot.to_string_lossy().into_owned());
+    }
+    args.push("--crate-type".to_string());
+    args.push(crate_type_name(crate_type).to_string());
+    args.extend(options.args.iter().cloned());
+    args.push(input_file_path.to_string_lossy().into_owned());
 
-    let matches = match rustc_driver::handle_options(args) {
+    let matches = match rustc_driver::handle_options(args.clone()) {
         Some(matches) => matches,
-        None => panic!("cant handle options")
+        None => return Err(Error::RustcArgs(args))
     };
 
     let sopts = config::build_session_options(&matches);
 
-    //let (odir, ofile) = make_output = {
-    //    let odir = &matches.opt_str("out-dir").map(|o| PathBuf::from(&o));
-    //    let ofile = &matches.opt_str("o").map(|o| PathBuf::from(&o));
-    //    (odir, ofile)
-    //};
-
-    //let (odir, ofile) = make_output(&matches);
-    //let (input, input_file_path) = match make_input(&matches.free) {
-    //    Some((input, input_file_path)) => callbacks.some_input(input, input_file_path),
-    //    None => match callbacks.no_input(&matches, &sopts, &odir, &ofile, &descriptions) {
-    //        Some((input, input_file_path)) => (input, input_file_path),
-    //        None => panic!("cant find input")
-    //    }
-    //};
-
-    //let sess = session::build_session(sopts, input_file_path, descriptions);
-
-    session::build_session(
-        sopts, Some(input_file_path), descriptions
-    )
+    let emitter = diagnostics.emitter(Box::new(EmitterWriter::stderr(sopts.color, Some(descriptions))));
+    let span_diagnostic = diagnostic::mk_span_handler(diagnostic::mk_handler(true, emitter), CodeMap::new());
+
+    Ok(session::build_session_(
+        sopts, Some(input_file_path), span_diagnostic
+    ))
+}
+
+
+fn crate_type_name(crate_type: CrateType) -> &'static str {
+    match crate_type {
+        CrateType::Executable => "bin",
+        CrateType::Dylib => "dylib",
+        CrateType::Rlib => "rlib",
+        CrateType::Staticlib => "staticlib"
+    }
+}
+
+
+/// Sysroot of the active toolchain, i.e. of `$RUSTC` or `rustc` found in `PATH`.
+pub fn detect_sysroot() -> Option<PathBuf> {
+    use std::env;
+    use std::process::Command;
+
+    let rustc = env::var("RUSTC").unwrap_or("rustc".to_string());
+    Command::new(&rustc).arg("--print").arg("sysroot").output().ok()
+        .and_then(|output| if output.status.success() { String::from_utf8(output.stdout).ok() } else { None })
+        .map(|stdout| stdout.trim().to_string())
+        .and_then(|sysroot| if sysroot.is_empty() { None } else { Some(PathBuf::from(sysroot)) })
 }
 
 
@@ -131,24 +192,48 @@ fn make_input(free_matches: &[String]) -> Option<(config::Input, Option<PathBuf>
 
 #[cfg(test)]
 mod tests {
-    use std::path::Path;
+    use std::fs;
+    use std::path::PathBuf;
+    use explorer::CrateJob;
+    use manifest::{CrateTarget, TargetKind};
     use super::{
-        CtxtArenas, Forest, build_session, get_main_file_path, parse_and_expand,
-        assign_node_ids_and_map, analyze
+        CrateType, CtxtArenas, Forest, build_session, get_main_file_path,
+        parse_and_expand, assign_node_ids_and_map, analyze, target_crate_type
     };
+    use diagnostics::Diagnostics;
 
     #[test]
     fn test() {
-        let crate_path = Path::new("/tmp/hello_world/");
+        let job = CrateJob::fixture("hello_world", CrateType::Executable, &[
+            ("main.rs", "fn main() {\n    println!(\"Hello, world!\");\n}\n")
+        ]);
         let (source_path, crate_type) =
-            get_main_file_path(crate_path).expect("Can't find main file.");
+            get_main_file_path(&job.input).expect("Can't find main file.");
+        assert_eq!(crate_type, CrateType::Executable);
 
-        let sess = build_session(source_path.clone(), crate_type);
-        let (id, expanded_crate) = parse_and_expand(&sess, &source_path).unwrap();
+        let sess = build_session(source_path.clone(), crate_type, &job.compiler_options, &Diagnostics::new()).unwrap();
+        let (id, expanded_crate, _) = parse_and_expand(&sess, &source_path).unwrap();
 
         let mut forest = Forest::new(expanded_crate);
         let arenas = CtxtArenas::new();
         let map = assign_node_ids_and_map(&sess, &mut forest);
         let analysis = analyze(sess, id, map, &arenas);
+        assert_eq!(analysis.name, "main");
+        fs::remove_dir_all(&job.input).unwrap();
+    }
+
+    #[test]
+    fn test_target_crate_type() {
+        let target = |kind: TargetKind, crate_types: &[&str]| CrateTarget {
+            kind: kind,
+            name: "x".to_string(),
+            src_path: PathBuf::from("src/lib.rs"),
+            crate_types: crate_types.iter().map(|x| x.to_string()).collect()
+        };
+        assert_eq!(target_crate_type(&target(TargetKind::Lib, &["lib"])), CrateType::Rlib);
+        assert_eq!(target_crate_type(&target(TargetKind::Lib, &[])), CrateType::Rlib);
+        assert_eq!(target_crate_type(&target(TargetKind::Lib, &["proc-macro"])), CrateType::Dylib);
+        assert_eq!(target_crate_type(&target(TargetKind::Lib, &["staticlib", "rlib"])), CrateType::Staticlib);
+        assert_eq!(target_crate_type(&target(TargetKind::Bin, &["bin"])), CrateType::Executable);
     }
 }
@@ -1,4 +1,5 @@
 use std::collections::LinkedList;
+use std::collections::linked_list::Iter;
 use std::iter::FromIterator;
 
 
@@ -22,6 +23,11 @@ impl<T> Stack<T> {
         self.0.back()
     }
 
+    /// Iterates from the bottom to the top of the stack.
+    pub fn iter(&self) -> Iter<T> {
+        self.0.iter()
+    }
+
     pub fn len(&self) -> usize {
         self.0.len()
     }
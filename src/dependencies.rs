@@ -0,0 +1,179 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+
+/// Already built crates found in `target/debug/deps`-like directories.
+pub struct Dependencies {
+    pub search_paths: Vec<PathBuf>,
+    /// Library files by crate name, there may be several builds of a crate, e.g. of its different versions.
+    pub externs: BTreeMap<String, Vec<PathBuf>>
+}
+
+
+impl Dependencies {
+    pub fn find(dirs: &[PathBuf]) -> Dependencies {
+        let mut externs = BTreeMap::new();
+        for dir in dirs {
+            let entries = match fs::read_dir(dir) {
+                Ok(entries) => entries,
+                Err(_) => continue
+            };
+            let mut paths = entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect::<Vec<_>>();
+            paths.sort();
+            for path in paths {
+                let name = path.file_name().and_then(|name| name.to_str()).and_then(crate_name_of);
+                if let Some(name) = name {
+                    externs.entry(name.to_string()).or_insert_with(|| Vec::new()).push(path.clone());
+                }
+            }
+        }
+        Dependencies { search_paths: dirs.to_vec(), externs: externs }
+    }
+
+    /// Keeps only the builds of the resolved versions of crates, given the roots of their packages
+    /// (see `manifest::resolved_libraries`). A build is recognized by its sources, which are listed
+    /// in the dep-info file cargo writes next to it.
+    pub fn keep_resolved(&mut self, libraries: &BTreeMap<String, PathBuf>) {
+        for (name, builds) in self.externs.iter_mut() {
+            let root = match libraries.get(name) {
+                Some(root) if builds.len() > 1 => root,
+                _ => continue
+            };
+            let resolved = builds.iter().filter(|build| built_from(build, root)).cloned().collect::<Vec<_>>();
+            if !resolved.is_empty() {
+                *builds = resolved;
+            }
+        }
+    }
+
+    /// Names of crates still built more than once. Rustc can't choose between the builds,
+    /// so none of them is passed: the one to use has to be given with `--extern`.
+    pub fn ambiguous(&self) -> Vec<&str> {
+        self.externs.iter()
+            .filter(|&(_, builds)| builds.len() > 1)
+            .map(|(name, _)| &name[..])
+            .collect()
+    }
+
+    pub fn rustc_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        for path in self.search_paths.iter() {
+            args.push("-L".to_string());
+            args.push(format!("dependency={}", path.to_string_lossy()));
+        }
+        for (name, builds) in self.externs.iter() {
+            if builds.len() == 1 {
+                args.push("--extern".to_string());
+                args.push(format!("{}={}", name, builds[0].to_string_lossy()));
+            }
+        }
+        args
+    }
+}
+
+
+/// Conventional locations of built dependencies of the cargo project.
+pub fn default_dirs(project: &Path) -> Vec<PathBuf> {
+    vec![project.join("target/debug/deps"), project.join("target/debug")].into_iter()
+        .filter(|dir| fs::metadata(dir).map(|metadata| metadata.is_dir()).unwrap_or(false))
+        .collect()
+}
+
+
+/// `libserde_json-1a2b3c.rlib` -> `serde_json`
+fn crate_name_of(file_name: &str) -> Option<&str> {
+    if !file_name.starts_with("lib") || !file_name.ends_with(".rlib") {
+        return None;
+    }
+    let stem = &file_name[3..file_name.len() - ".rlib".len()];
+    let name = stem.rfind('-').map_or(stem, |index| &stem[..index]);
+    if name.is_empty() { None } else { Some(name) }
+}
+
+
+/// Whether the library was built from the sources under `root`: `deps/libfoo-1a2b3c.rlib`
+/// is described by `deps/foo-1a2b3c.d`, which lists the sources as `path: dependencies`.
+fn built_from(library: &Path, root: &Path) -> bool {
+    let dep_info = match library.file_stem().and_then(|stem| stem.to_str()) {
+        Some(stem) if stem.starts_with("lib") => library.with_file_name(format!("{}.d", &stem[3..])),
+        _ => return false
+    };
+    let mut content = String::new();
+    if File::open(&dep_info).and_then(|mut file| file.read_to_string(&mut content)).is_err() {
+        return false;
+    }
+    content.split_whitespace()
+        .map(|word| Path::new(word.trim_right_matches(':')))
+        .any(|path| path.starts_with(root))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::path::PathBuf;
+    use fixture::{temp_dir, write};
+    use super::{Dependencies, crate_name_of};
+
+    #[test]
+    fn test_find() {
+        let dir = temp_dir("dependencies");
+        for name in &["libgetopts-1a2b3c.rlib", "getopts-1a2b3c.d", "liblog-4d5e6f.rlib", "build-script"] {
+            write(&dir.join(name), "");
+        }
+
+        let dependencies = Dependencies::find(&[dir.clone(), dir.join("missing")]);
+        assert_eq!(dependencies.externs.keys().map(|x| &x[..]).collect::<Vec<_>>(), vec!["getopts", "log"]);
+        assert!(dependencies.ambiguous().is_empty());
+        assert_eq!(dependencies.rustc_args(), vec![
+            "-L".to_string(), format!("dependency={}", dir.display()),
+            "-L".to_string(), format!("dependency={}", dir.join("missing").display()),
+            "--extern".to_string(), format!("getopts={}", dir.join("libgetopts-1a2b3c.rlib").display()),
+            "--extern".to_string(), format!("log={}", dir.join("liblog-4d5e6f.rlib").display())
+        ]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_keep_resolved() {
+        let dir = temp_dir("resolved_dependencies");
+        let registry = PathBuf::from("/registry/src");
+        let builds = [
+            ("log", "a1", "log-0.3.1"), ("log", "b2", "log-0.4.0"), ("libc", "c3", "libc-0.2.0"), ("libc", "d4", "libc-0.2.0")
+        ];
+        for &(name, hash, package) in &builds {
+            let sources = registry.join(package).join("src");
+            write(&dir.join(format!("lib{}-{}.rlib", name, hash)), "");
+            write(&dir.join(format!("{}-{}.d", name, hash)), &format!(
+                "{}: {} {}\n", dir.join(format!("lib{}-{}.rlib", name, hash)).display(),
+                sources.join("lib.rs").display(), sources.join("macros.rs").display()
+            ));
+        }
+
+        let mut dependencies = Dependencies::find(&[dir.clone()]);
+        assert_eq!(dependencies.ambiguous(), vec!["libc", "log"]);
+        let mut libraries = BTreeMap::new();
+        libraries.insert("log".to_string(), registry.join("log-0.4.0"));
+        libraries.insert("libc".to_string(), registry.join("libc-0.2.0"));
+        dependencies.keep_resolved(&libraries);
+
+        // both builds of libc are of the resolved version, e.g. with different features
+        assert_eq!(dependencies.ambiguous(), vec!["libc"]);
+        assert_eq!(dependencies.rustc_args()[2..].to_vec(), vec![
+            "--extern".to_string(), format!("log={}", dir.join("liblog-b2.rlib").display())
+        ]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_crate_name_of() {
+        assert_eq!(crate_name_of("libserde_json-1a2b3c.rlib"), Some("serde_json"));
+        assert_eq!(crate_name_of("libgetopts.rlib"), Some("getopts"));
+        assert_eq!(crate_name_of("libgetopts-1a2b3c.so"), None);
+        assert_eq!(crate_name_of("getopts-1a2b3c.d"), None);
+        assert_eq!(crate_name_of("lib.rlib"), None);
+    }
+}
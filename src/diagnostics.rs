@@ -0,0 +1,229 @@
+use std::sync::{Arc, Mutex};
+use syntax::codemap::{CodeMap, Span};
+use syntax::diagnostic::{Emitter, Level, RenderSpan};
+use log;
+use navigation::{Region, span_to_region};
+use references::line_and_snippet;
+use html;
+
+
+/// Warning or error reported by the compiler.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub level: String,
+    pub message: String,
+    pub code: Option<String>,
+    pub region: Option<Region>
+}
+
+
+impl Diagnostic {
+    /// Class of the rendered diagnostic: `error`, `warning` or `note`.
+    pub fn class(&self) -> &'static str {
+        match &self.level[..] {
+            "warning" => "warning",
+            "note" | "help" => "note",
+            _ => "error"
+        }
+    }
+
+    /// Text shown on hover.
+    pub fn title(&self) -> String {
+        match self.code {
+            Some(ref code) => format!("{}[{}]: {}", self.level, code, self.message),
+            None => format!("{}: {}", self.level, self.message)
+        }
+    }
+}
+
+
+/// Diagnostics collected by the session. It is shared with the thread running the analysis,
+/// so messages are kept even if the analysis fails.
+#[derive(Clone)]
+pub struct Diagnostics(Arc<Mutex<Vec<Diagnostic>>>);
+
+
+impl Diagnostics {
+    pub fn new() -> Diagnostics {
+        Diagnostics(Arc::new(Mutex::new(Vec::new())))
+    }
+
+    /// Emitter which records diagnostics and passes them to `inner`,
+    /// only errors are passed at the `Quiet` log level.
+    pub fn emitter(&self, inner: Box<Emitter + Send>) -> Box<Emitter + Send> {
+        Box::new(CapturingEmitter { diagnostics: self.clone(), inner: inner, forwarding: true })
+    }
+
+    pub fn all(&self) -> Vec<Diagnostic> {
+        self.0.lock().unwrap().clone()
+    }
+
+    fn push(&self, diagnostic: Diagnostic) {
+        self.0.lock().unwrap().push(diagnostic);
+    }
+}
+
+
+struct CapturingEmitter {
+    diagnostics: Diagnostics,
+    inner: Box<Emitter + Send>,
+    /// Whether the last diagnostic was passed to `inner`, its notes follow it.
+    forwarding: bool
+}
+
+
+impl CapturingEmitter {
+    fn forwards(&mut self, lvl: Level) -> bool {
+        match lvl {
+            Level::Note | Level::Help => (),
+            Level::Warning => self.forwarding = log::enabled(log::Level::Normal),
+            _ => self.forwarding = true
+        }
+        self.forwarding
+    }
+
+    fn record(&self, codemap: Option<&CodeMap>, span: Option<Span>, msg: &str, code: Option<&str>, lvl: Level) {
+        // notes and help are attached to the previous diagnostic by rustc, they are shown as is
+        self.diagnostics.push(Diagnostic {
+            level: lvl.to_string(),
+            message: msg.to_string(),
+            code: code.map(|code| code.to_string()),
+            region: match (codemap, span) {
+                (Some(codemap), Some(span)) => span_to_region(codemap, span),
+                _ => None
+            }
+        });
+    }
+}
+
+
+impl Emitter for CapturingEmitter {
+    fn emit(&mut self, cmsp: Option<(&CodeMap, Span)>, msg: &str, code: Option<&str>, lvl: Level) {
+        self.record(cmsp.map(|(codemap, _)| codemap), cmsp.map(|(_, span)| span), msg, code, lvl);
+        if self.forwards(lvl) {
+            self.inner.emit(cmsp, msg, code, lvl);
+        }
+    }
+
+    fn custom_emit(&mut self, codemap: &CodeMap, sp: RenderSpan, msg: &str, lvl: Level) {
+        let span = match sp {
+            RenderSpan::FullSpan(span) |
+            RenderSpan::EndSpan(span) |
+            RenderSpan::Suggestion(span, _) |
+            RenderSpan::FileLine(span) => span
+        };
+        self.record(Some(codemap), Some(span), msg, None, lvl);
+        if self.forwards(lvl) {
+            self.inner.custom_emit(codemap, sp, msg, lvl);
+        }
+    }
+}
+
+
+/// Summary of the diagnostics of the file shown at the top of the page. `src` is the source of the page.
+pub fn render_diagnostics(diagnostics: &[Diagnostic], src: &str) -> String {
+    if diagnostics.is_empty() {
+        return String::new();
+    }
+
+    let count = |class: &str| diagnostics.iter().filter(|x| x.class() == class).count();
+    let mut items = format!(
+        "<li class=\"summary\">{}, {}</li>", plural(count("error"), "error"), plural(count("warning"), "warning")
+    );
+    for diagnostic in diagnostics {
+        let location = match diagnostic.region {
+            Some(ref region) if region.start <= src.len() => {
+                let line = line_and_snippet(src, region.start).0;
+                format!(" <a href=\"#L{0}\">line {0}</a>", line)
+            },
+            _ => String::new()
+        };
+        items.push_str(&format!(
+            "<li class=\"{}\">{}{}</li>", diagnostic.class(), html::escape(&diagnostic.title()), location
+        ));
+    }
+    format!("<ul class=\"diagnostics\">{}</ul>", items)
+}
+
+
+/// `1 error`, `2 errors`
+fn plural(count: usize, noun: &str) -> String {
+    format!("{} {}{}", count, noun, if count == 1 { "" } else { "s" })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use syntax::codemap::{CodeMap, Span};
+    use syntax::diagnostic::{Emitter, Level, RenderSpan};
+    use log;
+    use super::{Diagnostic, Diagnostics, plural, render_diagnostics};
+    use navigation::Region;
+
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl Emitter for Recorder {
+        fn emit(&mut self, _: Option<(&CodeMap, Span)>, msg: &str, _: Option<&str>, _: Level) {
+            self.0.lock().unwrap().push(msg.to_string());
+        }
+
+        fn custom_emit(&mut self, _: &CodeMap, _: RenderSpan, msg: &str, _: Level) {
+            self.0.lock().unwrap().push(msg.to_string());
+        }
+    }
+
+    #[test]
+    fn test_quiet_emitter() {
+        let printed = Arc::new(Mutex::new(Vec::new()));
+        let diagnostics = Diagnostics::new();
+        let mut emitter = diagnostics.emitter(Box::new(Recorder(printed.clone())));
+
+        log::set_level(log::Level::Quiet);
+        emitter.emit(None, "unused variable", None, Level::Warning);
+        emitter.emit(None, "consider using `_x`", None, Level::Help);
+        emitter.emit(None, "mismatched types", None, Level::Error);
+        emitter.emit(None, "expected `u8`", None, Level::Note);
+        log::set_level(log::Level::Normal);
+        emitter.emit(None, "dead code", None, Level::Warning);
+
+        assert_eq!(*printed.lock().unwrap(), vec!["mismatched types", "expected `u8`", "dead code"]);
+        assert_eq!(diagnostics.all().len(), 5);
+    }
+
+    #[test]
+    fn test_render_diagnostics() {
+        const SOURCE: &'static str = "fn main() {\n    let x: u8 = \"<x>\";\n}\n";
+        let diagnostics = vec![
+            Diagnostic {
+                level: "error".to_string(),
+                message: "mismatched types: expected `u8`, found `&'static str`".to_string(),
+                code: Some("E0308".to_string()),
+                region: Some(Region { filename: "main.rs".to_string(), start: 28, end: 32 })
+            },
+            Diagnostic {
+                level: "warning".to_string(),
+                message: "<unknown>".to_string(),
+                code: None,
+                region: None
+            }
+        ];
+
+        assert_eq!(render_diagnostics(&diagnostics, SOURCE), concat!(
+            "<ul class=\"diagnostics\">",
+            "<li class=\"summary\">1 error, 1 warning</li>",
+            "<li class=\"error\">error[E0308]: mismatched types: expected `u8`, ",
+            "found `&amp;'static str` <a href=\"#L2\">line 2</a></li>",
+            "<li class=\"warning\">warning: &lt;unknown&gt;</li>",
+            "</ul>"
+        ));
+        assert_eq!(render_diagnostics(&[], SOURCE), "");
+    }
+
+    #[test]
+    fn test_plural() {
+        assert_eq!(plural(0, "error"), "0 errors");
+        assert_eq!(plural(1, "error"), "1 error");
+        assert_eq!(plural(2, "warning"), "2 warnings");
+    }
+}
@@ -0,0 +1,88 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::result;
+use manifest::ManifestError;
+use output::OutputError;
+
+
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing the file failed.
+    Io(PathBuf, io::Error),
+    TemplateMissing(PathBuf),
+    /// Neither `main.rs` nor `lib.rs` is in the input directory and it isn't a cargo project.
+    NoCrateRoot(PathBuf),
+    Manifest(ManifestError),
+    /// Rustc rejected the arguments built from the options.
+    RustcArgs(Vec<String>),
+    /// The crate can't be parsed or expanded.
+    Parse(String),
+    /// The compiler aborted the analysis of the crate, e.g. on a type error.
+    Analysis(String),
+    /// The file isn't under the input directory, so it has no place in the output.
+    OutsideOfInput(PathBuf, PathBuf),
+    /// The server can't listen on the address.
+    Serve(String, io::Error)
+}
+
+
+pub type Result<T> = result::Result<T, Error>;
+
+
+impl Error {
+    pub fn io(path: &Path, error: io::Error) -> Error {
+        Error::Io(path.to_path_buf(), error)
+    }
+}
+
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref path, ref error) => write!(f, "{}: {}", path.display(), error),
+            Error::TemplateMissing(ref path) => write!(f, "template {} not found", path.display()),
+            Error::NoCrateRoot(ref path) =>
+                write!(f, "{} has neither Cargo.toml nor main.rs or lib.rs", path.display()),
+            Error::Manifest(ref error) => write!(f, "can't read manifest {}", error),
+            Error::RustcArgs(ref args) => write!(f, "rustc doesn't accept arguments `{}`", args.connect(" ")),
+            Error::Parse(ref name) => write!(f, "can't parse {}", name),
+            Error::Analysis(ref name) => write!(f, "analysis of {} failed", name),
+            Error::OutsideOfInput(ref path, ref input) =>
+                write!(f, "{} is outside of the input directory {}", path.display(), input.display()),
+            Error::Serve(ref address, ref error) => write!(f, "can't listen on {}: {}", address, error)
+        }
+    }
+}
+
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::Io(_, ref error) => error::Error::description(error),
+            Error::TemplateMissing(_) => "template not found",
+            Error::NoCrateRoot(_) => "crate root not found",
+            Error::Manifest(_) => "invalid manifest",
+            Error::RustcArgs(_) => "invalid rustc arguments",
+            Error::Parse(_) => "parsing failed",
+            Error::Analysis(_) => "analysis failed",
+            Error::OutsideOfInput(_, _) => "path outside of the input directory",
+            Error::Serve(_, ref error) => error::Error::description(error)
+        }
+    }
+}
+
+
+impl From<OutputError> for Error {
+    fn from(err: OutputError) -> Error {
+        Error::Io(err.path, err.error)
+    }
+}
+
+
+impl From<ManifestError> for Error {
+    fn from(err: ManifestError) -> Error {
+        Error::Manifest(err)
+    }
+}
@@ -0,0 +1,235 @@
+use std::path::PathBuf;
+use compiler_api::{CrateType, CompilerOptions, detect_sysroot, get_main_file_path, target_crate_type};
+use dependencies::{self, Dependencies};
+use errors::{Error, Result};
+use index::Index;
+use json_index::write_json;
+use lsif::write_lsif;
+use manifest::{MANIFEST, TargetKind, discover, is_cargo_project, resolved_libraries};
+use site::{Stats, write_site};
+use tags::{write_ctags, write_etags};
+
+
+/// What is written for every crate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// Pages of the files with navigation, see `site`.
+    Html,
+    /// `index.json` with all of the index, see `json_index`.
+    Json,
+    /// `dump.lsif` with the navigation graph, see `lsif`.
+    Lsif,
+    /// `tags` file for Vim, see `tags`.
+    Ctags,
+    /// `TAGS` file for Emacs.
+    Etags
+}
+
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "html" => Some(Format::Html),
+            "json" => Some(Format::Json),
+            "lsif" => Some(Format::Lsif),
+            "ctags" => Some(Format::Ctags),
+            "etags" => Some(Format::Etags),
+            _ => None
+        }
+    }
+}
+
+
+/// What to explore and how to render it.
+#[derive(Clone)]
+pub struct Config {
+    /// Cargo project, or directory with `main.rs` or `lib.rs`.
+    pub input: PathBuf,
+    pub output: PathBuf,
+    /// `None` for the built-in template.
+    pub template: Option<PathBuf>,
+    pub format: Format,
+    /// Pattern of links to items of other crates, see `ExternalLinks`.
+    pub extern_url: Option<String>,
+    /// Crates explored before and the urls of their output.
+    pub extern_explored: Vec<(String, String)>,
+    /// Remove `.html` files left in the output by previous runs.
+    pub clean: bool,
+    pub sysroot: Option<PathBuf>,
+    pub rustc_args: Vec<String>,
+    pub features: Vec<String>,
+    pub all_features: bool,
+    pub no_default_features: bool,
+    /// `key` or `key=value` specs passed to rustc as `--cfg`.
+    pub cfgs: Vec<String>,
+    /// Directories with built dependencies.
+    pub deps: Vec<PathBuf>
+}
+
+
+impl Config {
+    pub fn new(input: PathBuf, output: PathBuf, template: PathBuf) -> Config {
+        Config {
+            input: input,
+            output: output,
+            template: Some(template),
+            format: Format::Html,
+            extern_url: None,
+            extern_explored: Vec::new(),
+            clean: false,
+            sysroot: None,
+            rustc_args: Vec::new(),
+            features: Vec::new(),
+            all_features: false,
+            no_default_features: false,
+            cfgs: Vec::new(),
+            deps: Vec::new()
+        }
+    }
+}
+
+
+/// Crate to explore and the place for its rendered files.
+#[derive(Clone)]
+pub struct CrateJob {
+    pub name: String,
+    pub source_path: PathBuf,
+    pub crate_type: CrateType,
+    pub compiler_options: CompilerOptions,
+    /// Root of the sources, paths of the rendered files are relative to it.
+    pub input: PathBuf,
+    pub output: PathBuf
+}
+
+
+#[cfg(test)]
+impl CrateJob {
+    /// Crate made of `files` in a fresh temp dir, the first file is its root.
+    pub fn fixture(name: &str, crate_type: CrateType, files: &[(&str, &str)]) -> CrateJob {
+        let dir = ::fixture::temp_files(name, files);
+        CrateJob {
+            name: name.to_string(),
+            source_path: dir.join(files[0].0),
+            crate_type: crate_type,
+            compiler_options: CompilerOptions { sysroot: detect_sysroot(), args: Vec::new() },
+            input: dir.clone(),
+            output: dir.join("out")
+        }
+    }
+}
+
+
+/// Writes the index of the crate to `job.output` in the configured format.
+pub fn write_index(config: &Config, job: &CrateJob, index: &Index) -> Result<Stats> {
+    match config.format {
+        Format::Html => write_site(config, job, index),
+        Format::Json => write_json(job, index),
+        Format::Lsif => write_lsif(job, index),
+        Format::Ctags => write_ctags(job, index),
+        Format::Etags => write_etags(job, index)
+    }
+}
+
+
+/// Every target of every package for cargo projects, otherwise the crate with `main.rs` or `lib.rs`.
+pub fn crate_jobs(config: &Config) -> Result<Vec<CrateJob>> {
+    let compiler_options = compiler_options(config);
+
+    if !is_cargo_project(&config.input) {
+        if config.all_features || config.no_default_features {
+            log_info!(
+                "{} has no {}, --all-features and --no-default-features are ignored",
+                config.input.display(), MANIFEST
+            );
+        }
+        let (source_path, crate_type) = try!(
+            get_main_file_path(&config.input).ok_or_else(|| Error::NoCrateRoot(config.input.clone()))
+        );
+        return Ok(vec![CrateJob {
+            name: source_path.to_string_lossy().into_owned(),
+            source_path: source_path,
+            crate_type: crate_type,
+            compiler_options: with_features(&compiler_options, &config.features),
+            input: config.input.clone(),
+            output: config.output.clone()
+        }]);
+    }
+
+    let mut jobs = Vec::new();
+    for package in try!(discover(&config.input)) {
+        let features = package.resolve_features(
+            &config.features, config.all_features, config.no_default_features
+        );
+        let package_options = with_features(&compiler_options, &features);
+        for target in package.targets {
+            let target_dir = format!("{}-{}", target.kind.name(), target.name);
+            let mut target_options = package_options.clone();
+            if target.kind == TargetKind::Test || target.kind == TargetKind::Bench {
+                target_options.args.push("--test".to_string());
+            }
+            jobs.push(CrateJob {
+                name: format!("{} {}", package.name, target_dir),
+                crate_type: target_crate_type(&target),
+                compiler_options: target_options,
+                source_path: target.src_path,
+                input: package.root.clone(),
+                output: config.output.join(&package.name).join(target_dir)
+            });
+        }
+    }
+    Ok(jobs)
+}
+
+
+fn compiler_options(config: &Config) -> CompilerOptions {
+    let mut args = config.rustc_args.clone();
+    for spec in config.cfgs.iter() {
+        let mut parts = spec.splitn(2, '=');
+        args.push("--cfg".to_string());
+        args.push(cfg_spec(parts.next().unwrap(), parts.next()));
+    }
+
+    let deps_dirs = if config.deps.is_empty() && is_cargo_project(&config.input) {
+        dependencies::default_dirs(&config.input)
+    } else {
+        config.deps.clone()
+    };
+    let mut dependencies = Dependencies::find(&deps_dirs);
+    if !dependencies.ambiguous().is_empty() && is_cargo_project(&config.input) {
+        match resolved_libraries(&config.input) {
+            Ok(libraries) => dependencies.keep_resolved(&libraries),
+            Err(err) => log_verbose!("Can't resolve the dependencies: {}", err)
+        }
+    }
+    for name in dependencies.ambiguous() {
+        log_info!(
+            "Several builds of `{}` found, pass the one to use with `--rustc-arg=--extern={}=<path>`",
+            name, name
+        );
+    }
+    args.extend(dependencies.rustc_args());
+
+    CompilerOptions {
+        sysroot: config.sysroot.clone().or_else(detect_sysroot),
+        args: args
+    }
+}
+
+
+/// `--cfg` value: `key` or `key="value"`.
+fn cfg_spec(key: &str, value: Option<&str>) -> String {
+    match value {
+        Some(value) => format!("{}=\"{}\"", key.trim(), value.trim().trim_matches('"')),
+        None => key.trim().to_string()
+    }
+}
+
+
+fn with_features(compiler_options: &CompilerOptions, features: &[String]) -> CompilerOptions {
+    let mut compiler_options = compiler_options.clone();
+    for feature in features {
+        compiler_options.args.push("--cfg".to_string());
+        compiler_options.args.push(cfg_spec("feature", Some(feature)));
+    }
+    compiler_options
+}
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use navigation::ExternalItem;
+
+
+/// Name of the page that maps item paths of an explored crate to their definitions.
+pub const ITEMS_INDEX: &'static str = "items.html";
+
+
+/// Resolves links to items defined in other crates.
+///
+/// Crates that were explored too are linked through their items index,
+/// all the others through the url pattern (if any).
+pub struct ExternalLinks {
+    explored: HashMap<String, String>,
+    url_pattern: Option<String>
+}
+
+
+impl ExternalLinks {
+    pub fn new(explored: HashMap<String, String>, url_pattern: Option<String>) -> ExternalLinks {
+        ExternalLinks { explored: explored, url_pattern: url_pattern }
+    }
+
+    pub fn resolve(&self, item: &ExternalItem) -> Option<String> {
+        if let Some(root) = self.explored.get(&item.krate) {
+            return Some(format!(
+                "{}/{}#{}", root.trim_right_matches('/'), ITEMS_INDEX, item.full_path()
+            ));
+        }
+        self.url_pattern.as_ref().and_then(|pattern| expand_pattern(pattern, item))
+    }
+}
+
+
+/// Supported placeholders:
+///   {crate} - crate name, e.g. `collections`;
+///   {path}  - item path inside the crate, e.g. `vec::Vec`;
+///   {dir}   - parent modules separated by slashes, e.g. `vec`;
+///   {name}  - item name, e.g. `Vec`;
+///   {kind}  - item kind in terms of rustdoc, e.g. `struct`;
+///   {page}  - rustdoc page of the item, e.g. `vec/struct.Vec.html` or `vec/index.html` for modules.
+///
+/// Variants, methods and fields are documented on the page of their enum, struct or trait, so the
+/// placeholders refer to it and the anchor of the item is appended, e.g. `#variant.Some`.
+/// Methods of primitive types and the like aren't linked.
+fn expand_pattern(pattern: &str, item: &ExternalItem) -> Option<String> {
+    let (page_item, anchor) = match (item.kind, item.parent.as_ref()) {
+        ("variant", Some(parent)) | ("method", Some(parent)) | ("field", Some(parent)) => {
+            (&**parent, format!("#{}.{}", rustdoc_kind(item.kind), item.name()))
+        },
+        ("variant", None) | ("method", None) | ("field", None) => return None,
+        _ => (item, String::new())
+    };
+
+    let parents = &page_item.path[..page_item.path.len().saturating_sub(1)];
+    let dir = parents.connect("/");
+    let kind = rustdoc_kind(page_item.kind);
+    let page = if kind == "mod" {
+        format!("{}/index.html", page_item.path.connect("/"))
+    } else if dir.is_empty() {
+        format!("{}.{}.html", kind, page_item.name())
+    } else {
+        format!("{}/{}.{}.html", dir, kind, page_item.name())
+    };
+
+    let url = pattern
+        .replace("{crate}", &page_item.krate)
+        .replace("{path}", &page_item.full_path())
+        .replace("{dir}", &dir)
+        .replace("{name}", page_item.name())
+        .replace("{kind}", kind)
+        .replace("{page}", &page);
+    Some(url + &anchor)
+}
+
+
+/// Rustdoc names some kinds differently in the page names and anchors.
+fn rustdoc_kind(kind: &'static str) -> &'static str {
+    match kind {
+        "const" => "constant",
+        "field" => "structfield",
+        _ => kind
+    }
+}
+
+
+/// Renders page which redirects `items.html#module::Item` to the definition of the item.
+pub fn render_items_index<I>(items: I) -> String where I: IntoIterator<Item=(String, String)> {
+    let entries = items.into_iter()
+        .map(|(path, href)| format!("\"{}\": \"{}\"", escape_js(&path), escape_js(&href)))
+        .collect::<Vec<_>>();
+
+    format!(concat!(
+        "<html><head><script type=\"text/javascript\">\n",
+        "var items = {{{}}};\n",
+        "var href = items[window.location.hash.substr(1)];\n",
+        "if (href) {{ window.location.replace(href); }}\n",
+        "</script></head><body></body></html>\n"
+    ), entries.connect(", "))
+}
+
+
+fn escape_js(value: &str) -> String {
+    value.replace("\\", "\\\\").replace("\"", "\\\"").replace("<", "\\u003c")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use navigation::ExternalItem;
+    use super::ExternalLinks;
+
+    fn item(krate: &str, path: &[&str], kind: &'static str) -> ExternalItem {
+        ExternalItem {
+            krate: krate.into(),
+            path: path.iter().map(|name| name.to_string()).collect(),
+            kind: kind,
+            parent: None
+        }
+    }
+
+    fn vec_item() -> ExternalItem {
+        item("collections", &["vec", "Vec"], "struct")
+    }
+
+    #[test]
+    fn test_url_pattern() {
+        let links = ExternalLinks::new(
+            HashMap::new(), Some("doc/{crate}/{dir}/{kind}.{name}.html".into())
+        );
+        assert_eq!(links.resolve(&vec_item()), Some("doc/collections/vec/struct.Vec.html".into()));
+    }
+
+    #[test]
+    fn test_rustdoc_pages() {
+        let links = ExternalLinks::new(HashMap::new(), Some("doc/{crate}/{page}".into()));
+
+        let mut some = item("core", &["option", "Some"], "variant");
+        some.parent = Some(Box::new(item("core", &["option", "Option"], "enum")));
+        assert_eq!(links.resolve(&some), Some("doc/core/option/enum.Option.html#variant.Some".into()));
+
+        let mut push = item("collections", &["vec", "Vec<T>", "push"], "method");
+        push.parent = Some(Box::new(vec_item()));
+        assert_eq!(links.resolve(&push), Some("doc/collections/vec/struct.Vec.html#method.push".into()));
+
+        let mut start = item("core", &["ops", "Range", "start"], "field");
+        start.parent = Some(Box::new(item("core", &["ops", "Range"], "struct")));
+        assert_eq!(links.resolve(&start), Some("doc/core/ops/struct.Range.html#structfield.start".into()));
+        assert_eq!(links.resolve(&item("core", &["ops", "Range", "start"], "field")), None);
+
+        assert_eq!(links.resolve(&item("collections", &["vec"], "mod")), Some("doc/collections/vec/index.html".into()));
+        assert_eq!(links.resolve(&item("core", &["str", "len"], "method")), None);
+    }
+
+    #[test]
+    fn test_explored_crate() {
+        let mut explored = HashMap::new();
+        explored.insert("collections".to_string(), "../collections/".to_string());
+        let links = ExternalLinks::new(explored, Some("{name}".into()));
+        assert_eq!(links.resolve(&vec_item()), Some("../collections/items.html#vec::Vec".into()));
+    }
+
+    #[test]
+    fn test_unresolved() {
+        let links = ExternalLinks::new(HashMap::new(), None);
+        assert_eq!(links.resolve(&vec_item()), None);
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf, Component};
+use html;
+
+
+pub const INDEX_PAGE: &'static str = "index.html";
+
+
+/// Rendered files grouped by directories, paths are relative to the output root.
+#[derive(Debug, Default)]
+pub struct Directory {
+    pub dirs: BTreeMap<String, Directory>,
+    pub files: BTreeSet<String>
+}
+
+
+impl Directory {
+    pub fn from_paths<'a, I: IntoIterator<Item=&'a PathBuf>>(paths: I) -> Directory {
+        let mut root = Directory::default();
+        for path in paths {
+            let names = path.components()
+                .filter_map(|component| match component {
+                    Component::Normal(name) => name.to_str(),
+                    _ => None
+                })
+                .collect::<Vec<_>>();
+            root.insert(&names);
+        }
+        root
+    }
+
+    fn insert(&mut self, names: &[&str]) {
+        match names.len() {
+            0 => (),
+            1 => { self.files.insert(names[0].to_string()); },
+            _ => {
+                let dir = self.dirs.entry(names[0].to_string()).or_insert_with(|| Directory::default());
+                dir.insert(&names[1..]);
+            }
+        }
+    }
+
+    /// Subdirectory at `path` relative to this one.
+    pub fn find(&self, path: &Path) -> Option<&Directory> {
+        path.components().fold(Some(self), |directory, component| match (directory, component) {
+            (Some(directory), Component::Normal(name)) => name.to_str().and_then(|name| directory.dirs.get(name)),
+            _ => None
+        })
+    }
+
+    /// Calls `f` for this directory and every subdirectory with its path relative to the root.
+    pub fn walk<F: FnMut(&Path, &Directory)>(&self, f: &mut F) {
+        self.walk_from(&PathBuf::new(), f);
+    }
+
+    fn walk_from<F: FnMut(&Path, &Directory)>(&self, path: &Path, f: &mut F) {
+        f(path, self);
+        for (name, dir) in self.dirs.iter() {
+            dir.walk_from(&path.join(name), f);
+        }
+    }
+}
+
+
+/// Collapsible tree of all files, links are prefixed with `root`.
+pub fn render_tree(directory: &Directory, root: &str) -> String {
+    let mut buf = String::new();
+    buf.push_str("<ul class=\"file-tree\">");
+    render_entries(directory, root, &mut buf);
+    buf.push_str("</ul>");
+    buf
+}
+
+
+fn render_entries(directory: &Directory, prefix: &str, buf: &mut String) {
+    for (name, dir) in directory.dirs.iter() {
+        let dir_prefix = format!("{}{}/", prefix, name);
+        buf.push_str(&format!(
+            "<li class=\"dir collapsed\"><span class=\"dir-name\">{}</span><ul>",
+            html::escape(name)
+        ));
+        render_entries(dir, &dir_prefix, buf);
+        buf.push_str("</ul></li>");
+    }
+    for name in directory.files.iter() {
+        buf.push_str(&format!(
+            "<li class=\"file\"><a href=\"{}\">{}</a></li>",
+            html::escape_attribute(&format!("{}{}", prefix, name)), html::escape(source_name(name))
+        ));
+    }
+}
+
+
+/// Index page of the directory with links to its files and subdirectories.
+pub fn render_index(path: &Path, directory: &Directory) -> String {
+    let title = if path.components().count() == 0 {
+        "/".to_string()
+    } else {
+        format!("/{}/", path.to_string_lossy())
+    };
+
+    let mut items = String::new();
+    if path.components().count() > 0 {
+        items.push_str(&format!("<li class=\"dir\"><a href=\"../{}\">..</a></li>\n", INDEX_PAGE));
+    }
+    for name in directory.dirs.keys() {
+        items.push_str(&format!(
+            "<li class=\"dir\"><a href=\"{}/{}\">{}/</a></li>\n",
+            html::escape_attribute(name), INDEX_PAGE, html::escape(name)
+        ));
+    }
+    for name in directory.files.iter() {
+        items.push_str(&format!(
+            "<li class=\"file\"><a href=\"{}\">{}</a></li>\n",
+            html::escape_attribute(name), html::escape(source_name(name))
+        ));
+    }
+
+    format!(concat!(
+        "<html>\n<head><title>Index of {title}</title></head>\n<body>\n",
+        "<h1>Index of {title}</h1>\n<ul class=\"index\">\n{items}</ul>\n</body>\n</html>\n"
+    ), title = html::escape(&title), items = items)
+}
+
+
+/// `main.rs.html` -> `main.rs`
+fn source_name(name: &str) -> &str {
+    if name.ends_with(".html") { &name[..name.len() - ".html".len()] } else { name }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use super::{Directory, render_tree};
+
+    fn directory() -> Directory {
+        let paths = vec![PathBuf::from("lib.rs.html"), PathBuf::from("foo/bar.rs.html"), PathBuf::from("foo/mod.rs.html")];
+        Directory::from_paths(&paths)
+    }
+
+    #[test]
+    fn test_walk() {
+        let mut visited = Vec::new();
+        directory().walk(&mut |path: &Path, dir: &Directory| {
+            visited.push((path.to_path_buf(), dir.files.iter().cloned().collect::<Vec<_>>()));
+        });
+        assert_eq!(visited, vec![
+            (PathBuf::new(), vec!["lib.rs.html".to_string()]),
+            (PathBuf::from("foo"), vec!["bar.rs.html".to_string(), "mod.rs.html".to_string()]),
+        ]);
+    }
+
+    #[test]
+    fn test_find() {
+        let directory = directory();
+        assert_eq!(directory.find(Path::new("")).map(|dir| dir.files.len()), Some(1));
+        assert_eq!(directory.find(Path::new("foo")).map(|dir| dir.files.len()), Some(2));
+        assert!(directory.find(Path::new("foo/bar.rs.html")).is_none());
+        assert!(directory.find(Path::new("../foo")).is_none());
+    }
+
+    #[test]
+    fn test_render_tree() {
+        assert_eq!(render_tree(&directory(), "../"), concat!(
+            "<ul class=\"file-tree\">",
+            "<li class=\"dir collapsed\"><span class=\"dir-name\">foo</span><ul>",
+            "<li class=\"file\"><a href=\"../foo/bar.rs.html\">bar.rs</a></li>",
+            "<li class=\"file\"><a href=\"../foo/mod.rs.html\">mod.rs</a></li>",
+            "</ul></li>",
+            "<li class=\"file\"><a href=\"../lib.rs.html\">lib.rs</a></li>",
+            "</ul>"
+        ));
+    }
+}
@@ -0,0 +1,39 @@
+//! Files for tests. Only std is used, so that the integration tests can include the module too.
+
+#![allow(dead_code)] // not every test uses every helper
+
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+
+/// Empty `code_explorer_test_<name>` directory in the temp dir, leftovers of earlier runs are removed.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("code_explorer_test_{}", name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Temp dir with the files given as (relative path, content).
+pub fn temp_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = temp_dir(name);
+    for &(path, content) in files {
+        write(&dir.join(path), content);
+    }
+    dir
+}
+
+/// Writes the file, creating its parent directories.
+pub fn write(path: &Path, content: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    File::create(path).unwrap().write_all(content.as_bytes()).unwrap();
+}
+
+pub fn read(path: &Path) -> String {
+    let mut buf = String::new();
+    File::open(path).unwrap().read_to_string(&mut buf).unwrap();
+    buf
+}
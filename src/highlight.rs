@@ -0,0 +1,163 @@
+use lexer::Token;
+use syntax::parse::token::{DelimToken, Lit};
+use syntax::parse::token::Token as CompilerToken;
+
+
+/// Token with its highlighting class.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HighlightedToken {
+    pub start: usize, // inclusive byte offset from the start of the file
+    pub end: usize, // inclusive byte offset from the start of the file
+    pub class: Option<&'static str>
+}
+
+
+pub fn highlight(tokens: &[Token]) -> Vec<HighlightedToken> {
+    tokens.iter().zip(classify(tokens).into_iter())
+        .map(|(token, class)| HighlightedToken {
+            start: token.interval.lower_bound,
+            end: token.interval.upper_bound,
+            class: class
+        })
+        .collect()
+}
+
+
+/// Assigns a highlighting class to every token.
+///
+/// Besides the token itself the class depends on its neighbours:
+/// `#[...]` is highlighted as an attribute and `name!` as a macro.
+pub fn classify(tokens: &[Token]) -> Vec<Option<&'static str>> {
+    let mut classes = Vec::with_capacity(tokens.len());
+    let mut attribute_started = false;
+    let mut attribute_depth = 0;
+
+    for (index, token) in tokens.iter().enumerate() {
+        if attribute_depth == 0 && !attribute_started && starts_attribute(tokens, index) {
+            attribute_started = true;
+        }
+
+        if attribute_started || attribute_depth > 0 {
+            match token.compiler_token {
+                CompilerToken::OpenDelim(DelimToken::Bracket) => {
+                    attribute_started = false;
+                    attribute_depth += 1;
+                },
+                CompilerToken::CloseDelim(DelimToken::Bracket) if attribute_depth > 0 => {
+                    attribute_depth -= 1;
+                },
+                _ => ()
+            }
+            classes.push(if is_trivia(token) { classify_token(tokens, index) } else { Some("attribute") });
+        } else {
+            classes.push(classify_token(tokens, index));
+        }
+    }
+    classes
+}
+
+
+fn classify_token(tokens: &[Token], index: usize) -> Option<&'static str> {
+    let token = &tokens[index].compiler_token;
+    match *token {
+        CompilerToken::Ident(..) if token.is_any_keyword() => Some("keyword"),
+        CompilerToken::Ident(..) => {
+            if next_significant(tokens, index).map_or(false, |next| next.compiler_token == CompilerToken::Not) {
+                Some("macro")
+            } else {
+                None
+            }
+        },
+        CompilerToken::Not => {
+            let after_ident = previous_significant(tokens, index).map_or(false, |previous| {
+                match previous.compiler_token {
+                    CompilerToken::Ident(..) => !previous.compiler_token.is_any_keyword(),
+                    _ => false
+                }
+            });
+            Some(if after_ident { "macro" } else { "operator" })
+        },
+        CompilerToken::Literal(Lit::Str_(_), _) |
+        CompilerToken::Literal(Lit::StrRaw(..), _) |
+        CompilerToken::Literal(Lit::Binary(_), _) |
+        CompilerToken::Literal(Lit::BinaryRaw(..), _) => Some("string"),
+        CompilerToken::Literal(..) => Some("literal"),
+        CompilerToken::Lifetime(_) => Some("lifetime"),
+        CompilerToken::DocComment(_) => Some("doc-comment"),
+        CompilerToken::Comment | CompilerToken::Shebang(_) => Some("comment"),
+        CompilerToken::Eq | CompilerToken::Lt | CompilerToken::Le | CompilerToken::EqEq |
+        CompilerToken::Ne | CompilerToken::Ge | CompilerToken::Gt | CompilerToken::AndAnd |
+        CompilerToken::OrOr | CompilerToken::Tilde | CompilerToken::BinOp(_) |
+        CompilerToken::BinOpEq(_) | CompilerToken::RArrow | CompilerToken::LArrow |
+        CompilerToken::FatArrow | CompilerToken::DotDot | CompilerToken::DotDotDot => Some("operator"),
+        _ => None
+    }
+}
+
+
+/// `#[` or `#![`
+fn starts_attribute(tokens: &[Token], index: usize) -> bool {
+    if tokens[index].compiler_token != CompilerToken::Pound {
+        return false;
+    }
+    let mut rest = tokens[index + 1..].iter().filter(|token| !is_trivia(token));
+    match rest.next().map(|token| &token.compiler_token) {
+        Some(&CompilerToken::OpenDelim(DelimToken::Bracket)) => true,
+        Some(&CompilerToken::Not) => {
+            rest.next().map_or(false, |token| token.compiler_token == CompilerToken::OpenDelim(DelimToken::Bracket))
+        },
+        _ => false
+    }
+}
+
+
+fn next_significant(tokens: &[Token], index: usize) -> Option<&Token> {
+    tokens[index + 1..].iter().find(|token| !is_trivia(token))
+}
+
+
+fn previous_significant(tokens: &[Token], index: usize) -> Option<&Token> {
+    tokens[..index].iter().rev().find(|token| !is_trivia(token))
+}
+
+
+fn is_trivia(token: &Token) -> bool {
+    match token.compiler_token {
+        CompilerToken::Whitespace | CompilerToken::Comment => true,
+        _ => false
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use syntax::codemap::CodeMap;
+    use lexer::{read_tokens, IntervalToSnippet};
+    use super::classify;
+
+    const SOURCE: &'static str = "#[test]\nfn main() { println!(\"{}\", 'a' as u8 + 1); } // end\n";
+
+    #[test]
+    fn test_classify() {
+        let codemap = CodeMap::new();
+        let filemap = codemap.new_filemap("".into(), SOURCE.into());
+        let tokens = read_tokens(filemap.clone());
+        let classes = classify(&tokens);
+
+        let classified = tokens.iter().zip(classes.into_iter())
+            .filter_map(|(token, class)| class.map(
+                |class| (filemap.interval_to_snippet(&token.interval).unwrap(), class)
+            ))
+            .collect::<Vec<_>>();
+
+        assert_eq!(classified, vec![
+            ("#".to_string(), "attribute"), ("[".to_string(), "attribute"),
+            ("test".to_string(), "attribute"), ("]".to_string(), "attribute"),
+            ("fn".to_string(), "keyword"),
+            ("println".to_string(), "macro"), ("!".to_string(), "macro"),
+            ("\"{}\"".to_string(), "string"), ("'a'".to_string(), "literal"),
+            ("as".to_string(), "keyword"), ("+".to_string(), "operator"),
+            ("1".to_string(), "literal"), ("// end".to_string(), "comment"),
+        ]);
+    }
+}
@@ -8,13 +8,18 @@ pub struct Tag<T> {
     tag_type: T,
     classes: HashSet<String>,
     ids: HashSet<String>,
-    name: Option<String>
+    name: Option<String>,
+    title: Option<String>,
+    data: Vec<(String, String)>
 }
 
 
 impl<T: TagType> Tag<T> {
     pub fn new(tag_type: T) -> Tag<T> {
-        Tag { tag_type: tag_type, ids: HashSet::new(), classes: HashSet::new(), name: None }
+        Tag {
+            tag_type: tag_type, ids: HashSet::new(), classes: HashSet::new(),
+            name: None, title: None, data: Vec::new()
+        }
     }
 
     pub fn add_class<S: Into<String>>(mut self, class: S) -> Tag<T> {
@@ -32,6 +37,17 @@ impl<T: TagType> Tag<T> {
         self
     }
 
+    pub fn set_title<S: Into<String>>(mut self, title: S) -> Tag<T> {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Adds `data-<key>` attribute.
+    pub fn add_data<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Tag<T> {
+        self.data.push((format!("data-{}", key.into()), value.into()));
+        self
+    }
+
     pub fn render_open(&self) -> String {
         let attributes = self.render_attributes();
         format!(
@@ -57,6 +73,10 @@ impl<T: TagType> Tag<T> {
         if let Some(ref name) = self.name {
             attributes.push(("name".to_string(), name.clone()));
         }
+        if let Some(ref title) = self.title {
+            attributes.push(("title".to_string(), title.clone()));
+        }
+        attributes.extend(self.data.iter().cloned());
         attributes
     }
 
@@ -64,7 +84,7 @@ impl<T: TagType> Tag<T> {
         let attributes = self.attributes();
         let tag_type_attributes = self.tag_type.attributes();
         let iter = attributes.into_iter().chain(tag_type_attributes)
-            .map(|(keyword, value)| format!("{}=\"{}\"", keyword, value));
+            .map(|(keyword, value)| format!("{}=\"{}\"", keyword, escape_attribute(&value)));
         concat_chunks(iter, " ")
     }
 }
@@ -78,6 +98,34 @@ impl Tag<A> {
 }
 
 
+/// Escapes text to be placed inside of an element.
+pub fn escape(text: &str) -> String {
+    escape_chars(text, false)
+}
+
+
+/// Escapes text to be placed inside of a quoted attribute value.
+pub fn escape_attribute(text: &str) -> String {
+    escape_chars(text, true)
+}
+
+
+fn escape_chars(text: &str, quotes: bool) -> String {
+    let mut buf = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '<' => buf.push_str("&lt;"),
+            '>' => buf.push_str("&gt;"),
+            '&' => buf.push_str("&amp;"),
+            '"' if quotes => buf.push_str("&quot;"),
+            '\'' if quotes => buf.push_str("&#39;"),
+            _ => buf.push(ch)
+        }
+    }
+    buf
+}
+
+
 fn concat_chunks<I, T>(chunks: I, separator: &str) -> String where I: IntoIterator<Item=T>, T: AsRef<str> {
     let mut buf = String::new();
     let mut iter = chunks.into_iter();
@@ -155,6 +203,7 @@ pub mod tags {
 #[cfg(test)]
 mod tests {
     use super::tags::{Span, A};
+    use super::{escape, escape_attribute};
 
     #[test]
     fn test_simple_tag() {
@@ -170,10 +219,38 @@ mod tests {
         assert_eq!("</span>", tag.render_close());
     }
 
+    #[test]
+    fn test_data() {
+        let tag = Span::new().add_data("type", "u32");
+        assert_eq!(r#"<span data-type="u32">"#, tag.render_open());
+    }
+
     #[test]
     fn test_href() {
         let tag = A::new().set_href("foo");
         assert_eq!(r#"<a href="foo">"#, tag.render_open());
         assert_eq!("</a>", tag.render_close());
     }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("<script>a && b</script>"), "&lt;script&gt;a &amp;&amp; b&lt;/script&gt;");
+        assert_eq!(escape(r#"'quoted' "text""#), r#"'quoted' "text""#);
+        assert_eq!(escape_attribute(r#"a"b'c<d>&"#), "a&quot;b&#39;c&lt;d&gt;&amp;");
+    }
+
+    #[test]
+    fn test_escaped_attributes() {
+        let tag = A::new()
+            .add_class("x\" onclick=\"alert(1)")
+            .add_data("type", "&'static Vec<u8>")
+            .set_href("a.html?x=1&y=\"2\"");
+        assert_eq!(
+            concat!(
+                r#"<a class="x&quot; onclick=&quot;alert(1)" data-type="&amp;&#39;static Vec&lt;u8&gt;" "#,
+                r#"href="a.html?x=1&amp;y=&quot;2&quot;">"#
+            ),
+            tag.render_open()
+        );
+    }
 }
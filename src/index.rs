@@ -0,0 +1,309 @@
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use syntax::codemap::{CodeMap, FileMap};
+use compiler_api::{CtxtArenas, Forest, build_session, parse_and_expand, assign_node_ids_and_map, resolve, analyze};
+use diagnostics::{Diagnostic, Diagnostics};
+use errors::{Error, Result};
+use explorer::CrateJob;
+use highlight::{HighlightedToken, highlight};
+use lexer;
+use log::timed;
+use navigation::{
+    collect_mappings, collect_resolved, collect_inactive_regions, Mappings, Definition, ActiveRegion, TypeHint,
+    InactiveRegion
+};
+
+
+/// Everything known about a crate, regions refer to the files by their names.
+pub struct Index {
+    pub files: Vec<SourceFile>,
+    pub definitions: Vec<Definition>,
+    pub active_regions: Vec<ActiveRegion>,
+    pub type_hints: Vec<TypeHint>,
+    pub inactive_regions: Vec<InactiveRegion>,
+    pub diagnostics: Vec<Diagnostic>,
+    /// Why the crate couldn't be analyzed, there are no type hints and no links to other crates then.
+    pub failure: Option<Error>
+}
+
+
+pub struct SourceFile {
+    pub name: String,
+    pub src: String,
+    pub tokens: Vec<HighlightedToken>
+}
+
+
+impl Index {
+    /// Index of the files without any navigation.
+    pub fn new(files: Vec<SourceFile>) -> Index {
+        Index {
+            files: files,
+            definitions: Vec::new(),
+            active_regions: Vec::new(),
+            type_hints: Vec::new(),
+            inactive_regions: Vec::new(),
+            diagnostics: Vec::new(),
+            failure: None
+        }
+    }
+
+    pub fn file(&self, name: &str) -> Option<&SourceFile> {
+        self.files.iter().find(|file| file.name == name)
+    }
+}
+
+
+/// Analyzes the crate. If the analysis fails, the index keeps what was found before the failure:
+/// the sources of the crate, regions disabled by cfg attributes and the navigation to local definitions,
+/// or all the sources under the input if the crate can't be parsed. It's an error only if there are
+/// no sources to show.
+pub fn index_crate(job: &CrateJob) -> Result<Index> {
+    log_verbose!("explore {}", job.name);
+    let diagnostics = Diagnostics::new();
+    let partial = Arc::new(Mutex::new(None));
+    let (thread_job, thread_diagnostics, thread_partial) = (job.clone(), diagnostics.clone(), partial.clone());
+    // the compiler aborts by panicking, so the analysis runs in its own thread
+    let analysis = thread::spawn(move || analyze_crate(&thread_job, &thread_diagnostics, &thread_partial));
+    let failure = match analysis.join() {
+        Ok(Ok(index)) => return Ok(index),
+        Ok(Err(err)) => err,
+        Err(_) => Error::Analysis(job.name.clone())
+    };
+    log_info!("{}, rendering sources without full navigation", failure);
+
+    let partial = partial.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take();
+    let mut index = match partial.or_else(|| sources_index(job)) {
+        Some(index) => index,
+        None => return Err(failure)
+    };
+    index.diagnostics = diagnostics.all();
+    index.failure = Some(failure);
+    Ok(index)
+}
+
+
+/// `partial` gets the index without the results of the type checking as soon as it is known.
+fn analyze_crate(job: &CrateJob, diagnostics: &Diagnostics, partial: &Mutex<Option<Index>>) -> Result<Index> {
+    let source_path = &job.source_path;
+    let sess = try!(build_session(source_path.clone(), job.crate_type, &job.compiler_options, diagnostics));
+    let (id, expanded_crate, stripped) = try!(timed("parse and expand", || {
+        parse_and_expand(&sess, source_path).ok_or_else(|| Error::Parse(job.name.clone()))
+    }));
+    let files = timed("highlight", || filemaps(sess.codemap()).into_iter().map(source_file).collect());
+    let inactive_regions = collect_inactive_regions(sess.codemap(), stripped);
+
+    let mut forest = Forest::new(expanded_crate);
+    let arenas = CtxtArenas::new();
+    let map = timed("assign node ids", || assign_node_ids_and_map(&sess, &mut forest));
+
+    let def_map = timed("resolve", || resolve(&sess, &map));
+    let resolved = timed("collect resolved", || collect_resolved(&map, sess.codemap(), &def_map));
+    *partial.lock().unwrap() = Some(Index {
+        definitions: resolved.definitions,
+        active_regions: resolved.active_regions,
+        type_hints: resolved.type_hints,
+        inactive_regions: inactive_regions,
+        ..Index::new(files)
+    });
+
+    let analysis = timed("analysis", || analyze(sess, id, map, &arenas));
+    let Mappings { active_regions, definitions, type_hints } = timed("collect mappings", || collect_mappings(&analysis));
+
+    let mut index = partial.lock().unwrap().take().expect("index before the analysis");
+    index.definitions = definitions;
+    index.active_regions = active_regions;
+    index.type_hints = type_hints;
+    index.diagnostics = diagnostics.all();
+    Ok(index)
+}
+
+
+/// The files of the crate are unknown if it can't be parsed, so all the sources under the input
+/// are shown, the diagnostics may point to any of them.
+fn sources_index(job: &CrateJob) -> Option<Index> {
+    let mut paths = source_files(&job.input);
+    if !paths.contains(&job.source_path) {
+        paths.insert(0, job.source_path.clone());
+    }
+    let codemap = CodeMap::new();
+    let mut files = Vec::new();
+    for path in paths {
+        let mut src = String::new();
+        match File::open(&path).and_then(|mut file| file.read_to_string(&mut src)) {
+            Ok(_) => files.push(source_file(codemap.new_filemap(path.to_string_lossy().into_owned(), src))),
+            Err(err) => log_info!("Can't read {:?}: {}", path, err)
+        }
+    }
+    if files.is_empty() { None } else { Some(Index::new(files)) }
+}
+
+
+fn source_file(filemap: Rc<FileMap>) -> SourceFile {
+    let tokens = highlight(&lexer::read_tokens(filemap.clone()));
+    SourceFile {
+        name: filemap.name.clone(),
+        src: filemap.src.as_ref().map_or(String::new(), |src| src[..].to_string()),
+        tokens: tokens
+    }
+}
+
+
+fn filemaps(codemap: &CodeMap) -> Vec<Rc<FileMap>> {
+    let mut filemaps = Vec::new();
+    for fm in &*codemap.files.borrow() {
+        if fm.is_real_file() && fm.src != None {
+            filemaps.push(fm.clone());
+        }
+    }
+    filemaps
+}
+
+
+/// Rust files of the directory and its subdirectories, except of hidden ones and `target`.
+pub fn source_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return files
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        let skipped = path.file_name()
+            .and_then(|name| name.to_str())
+            .map_or(true, |name| name.starts_with(".") || name == "target");
+        if skipped {
+            continue;
+        }
+        if path.is_dir() {
+            files.extend(source_files(&path));
+        } else if path.extension().map_or(false, |ext| ext == "rs") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    files
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use compiler_api::CrateType;
+    use explorer::{Config, CrateJob, write_index};
+    use fixture::read;
+    use navigation::Target;
+    use super::index_crate;
+
+    fn job(name: &str, files: &[(&str, &str)]) -> CrateJob {
+        CrateJob::fixture(name, CrateType::Rlib, files)
+    }
+
+    #[test]
+    fn test_type_error_keeps_resolved_navigation() {
+        let job = job("type_error", &[
+            ("lib.rs", "mod util;\n\npub fn answer() -> u8 { util::helper() + \"x\" }\n"),
+            ("util.rs", "pub fn helper() -> u8 { 42 }\n"),
+            ("main.rs", "fn main() {}\n") // not a part of the crate
+        ]);
+        let index = index_crate(&job).unwrap();
+        assert!(index.failure.is_some());
+        assert!(index.diagnostics.iter().any(|x| x.class() == "error"));
+
+        let mut files = index.files.iter().map(|x| x.name.clone()).collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(files, vec![
+            job.input.join("lib.rs").to_string_lossy().into_owned(),
+            job.input.join("util.rs").to_string_lossy().into_owned()
+        ]);
+
+        let helper = index.definitions.iter().find(|x| x.path == Some("util::helper".to_string())).unwrap();
+        assert!(index.active_regions.iter().any(|x| match x.target {
+            Target::Definition(_, id) => id == helper.id,
+            Target::External(_) => false
+        }));
+        assert!(index.type_hints.is_empty());
+        fs::remove_dir_all(&job.input).unwrap();
+    }
+
+    #[test]
+    fn test_members() {
+        let src = concat!(
+            "pub struct Point { pub x: i32 }\n",
+            "impl Point { pub fn len(&self) -> i32 { self.x } }\n",
+            "pub fn norm(p: &Point) -> i32 { p.x + p.len() }\n"
+        );
+        let job = job("members", &[("lib.rs", src)]);
+        let index = index_crate(&job).unwrap();
+        fs::remove_dir_all(&job.input).unwrap();
+        assert!(index.failure.is_none());
+
+        let definition_at = |name: &str, from: usize| {
+            let start = src[from..].find(name).unwrap() + from;
+            index.definitions.iter().find(|x| x.region.start == start && x.region.end == start + name.len() - 1)
+                .map(|x| x.id)
+        };
+        let target_at = |name: &str, from: usize| {
+            let start = src[from..].find(name).unwrap() + from;
+            index.active_regions.iter().find(|x| x.region.start == start && x.region.end == start + name.len() - 1)
+                .and_then(|x| match x.target {
+                    Target::Definition(_, id) => Some(id),
+                    Target::External(_) => None
+                })
+        };
+        let norm = src.find("norm").unwrap();
+        let field = definition_at("x", 0);
+        let method = definition_at("len", 0);
+        assert!(field.is_some() && method.is_some());
+        assert_eq!(target_at("x", src.find("self.x").unwrap()), field);
+        assert_eq!(target_at("x", norm), field);
+        assert_eq!(target_at("len", norm), method);
+    }
+
+    #[test]
+    fn test_type_hints() {
+        let src = "pub fn first(v: &[u8]) -> bool {\n    let empty = v.is_empty();\n    !empty\n}\n";
+        let job = job("type_hints", &[("lib.rs", src)]);
+        let index = index_crate(&job).unwrap();
+        fs::remove_dir_all(&job.input).unwrap();
+
+        let hint_at = |name: &str| {
+            let start = src.find(name).unwrap();
+            index.type_hints.iter()
+                .find(|x| x.region.start == start && x.region.end == start + name.len() - 1)
+                .map(|x| &x.ty[..])
+        };
+        assert_eq!(hint_at("empty"), Some("bool"));
+        assert_eq!(hint_at("is_empty"), Some("bool"));
+        assert_eq!(hint_at("v"), Some("&[u8]"));
+    }
+
+    #[test]
+    fn test_parse_error_keeps_sources() {
+        let job = job("parse_error", &[
+            ("lib.rs", "mod util;\n\npub fn answer() -> u8 { 42 }\n"),
+            ("util.rs", "pub fn helper() -> u8 {\n    broken(\n}\n")
+        ]);
+        let index = index_crate(&job).unwrap();
+        assert!(index.failure.is_some());
+        assert_eq!(index.files.iter().map(|x| x.name.clone()).collect::<Vec<_>>(), vec![
+            job.input.join("lib.rs").to_string_lossy().into_owned(),
+            job.input.join("util.rs").to_string_lossy().into_owned()
+        ]);
+        let util_rs = job.input.join("util.rs").to_string_lossy().into_owned();
+        assert!(index.diagnostics.iter().any(|x| {
+            x.class() == "error" && x.region.as_ref().map_or(false, |region| region.filename == util_rs)
+        }));
+
+        let config = Config { template: None, ..Config::new(job.input.clone(), job.output.clone(), PathBuf::new()) };
+        write_index(&config, &job, &index).unwrap();
+        let util = read(&job.output.join("util.rs.html"));
+        assert!(util.contains("<li class=\"error\">"));
+        fs::remove_dir_all(&job.input).unwrap();
+    }
+}
@@ -0,0 +1,253 @@
+//! Index of the crate as JSON, for tools which need the cross-references without scraping html.
+//!
+//! `index.json` is written to the output directory of the crate. Offsets are inclusive byte
+//! offsets from the start of the file, the same as in `Region`. Files are referred by their
+//! names as given to the compiler.
+//!
+//! ```text
+//! {
+//!   "version": 1,
+//!   "crate": "foo bin-foo",              // name of the explored crate (target for cargo projects)
+//!   "failure": null,                     // why the analysis failed, "type_hints" are empty then
+//!   "files": [{
+//!     "name": "src/main.rs",
+//!     "tokens": [{"start": 0, "end": 1, "class": "keyword"}]   // class may be null
+//!   }],
+//!   "definitions": [{                   // all items of the crate and the referred bindings
+//!     "id": 1, "file": "src/main.rs", "start": 3, "end": 6,    // the name of the definition
+//!     "kind": "fn",                      // fn, struct, enum, variant, trait, type, const, static,
+//!                                        // mod, crate, method, field, local or item
+//!     "name": "main",
+//!     "path": "main"                     // item path from the crate root, null for bindings
+//!   }],
+//!   "active_regions": [{
+//!     "file": "src/main.rs", "start": 40, "end": 42,
+//!     "target": {"file": "src/lib.rs", "id": 1}
+//!        // or for items of other crates
+//!        // {"crate": "std", "path": "vec::Vec", "kind": "struct"}, kinds are the same as above
+//!   }],
+//!   "type_hints": [{"file": "src/main.rs", "start": 44, "end": 44, "type": "u8"}],
+//!   "inactive_regions": [{"file": "src/main.rs", "start": 50, "end": 80, "predicate": "windows"}],
+//!   "diagnostics": [{
+//!     "level": "warning", "message": "unused variable: `x`", "code": null,
+//!     "file": "src/main.rs", "start": 44, "end": 44   // null if the diagnostic has no location
+//!   }]
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use serialize::json::{Json, ToJson};
+use diagnostics::Diagnostic;
+use errors::Result;
+use explorer::CrateJob;
+use highlight::HighlightedToken;
+use index::Index;
+use navigation::{ActiveRegion, InactiveRegion, Region, Target, TypeHint};
+use output::OutputWriter;
+use site::Stats;
+
+
+pub const JSON_INDEX: &'static str = "index.json";
+const VERSION: u64 = 1;
+
+
+pub fn write_json(job: &CrateJob, index: &Index) -> Result<Stats> {
+    let mut writer = OutputWriter::new(job.output.clone());
+    try!(writer.write(Path::new(JSON_INDEX), &index_to_json(&job.name, index).pretty().to_string()));
+    Ok(Stats {
+        files: writer.written_count(),
+        definitions: index.definitions.len(),
+        links: index.active_regions.len(),
+        unapplied_wrappers: 0
+    })
+}
+
+
+pub fn index_to_json(crate_name: &str, index: &Index) -> Json {
+    let files = index.files.iter()
+        .map(|file| object(vec![
+            ("name", file.name.to_json()),
+            ("tokens", file.tokens.to_json())
+        ]))
+        .collect::<Vec<_>>();
+
+    let definitions = index.definitions.iter()
+        .map(|definition| {
+            let src = index.file(&definition.region.filename).map_or("", |file| &file.src[..]);
+            with_region(&definition.region, vec![
+                ("id", definition.id.to_json()),
+                ("kind", definition.kind.to_json()),
+                ("name", definition.name(src).to_json()),
+                ("path", definition.path.to_json())
+            ])
+        })
+        .collect::<Vec<_>>();
+
+    object(vec![
+        ("version", VERSION.to_json()),
+        ("crate", crate_name.to_json()),
+        ("failure", index.failure.as_ref().map(|failure| failure.to_string()).to_json()),
+        ("files", Json::Array(files)),
+        ("definitions", Json::Array(definitions)),
+        ("active_regions", index.active_regions.to_json()),
+        ("type_hints", index.type_hints.to_json()),
+        ("inactive_regions", index.inactive_regions.to_json()),
+        ("diagnostics", index.diagnostics.to_json())
+    ])
+}
+
+
+pub fn object(fields: Vec<(&str, Json)>) -> Json {
+    Json::Object(fields.into_iter().map(|(key, value)| (key.to_string(), value)).collect::<BTreeMap<_, _>>())
+}
+
+
+fn with_region(region: &Region, fields: Vec<(&str, Json)>) -> Json {
+    let mut all = vec![
+        ("file", region.filename.to_json()),
+        ("start", region.start.to_json()),
+        ("end", region.end.to_json())
+    ];
+    all.extend(fields.into_iter());
+    object(all)
+}
+
+
+impl ToJson for HighlightedToken {
+    fn to_json(&self) -> Json {
+        object(vec![
+            ("start", self.start.to_json()),
+            ("end", self.end.to_json()),
+            ("class", self.class.map(|class| class.to_string()).to_json())
+        ])
+    }
+}
+
+
+impl ToJson for ActiveRegion {
+    fn to_json(&self) -> Json {
+        let target = match self.target {
+            Target::Definition(ref filename, id) => object(vec![
+                ("file", filename.to_json()),
+                ("id", id.to_json())
+            ]),
+            Target::External(ref item) => object(vec![
+                ("crate", item.krate.to_json()),
+                ("path", item.full_path().to_json()),
+                ("kind", item.kind.to_json())
+            ])
+        };
+        with_region(&self.region, vec![("target", target)])
+    }
+}
+
+
+impl ToJson for TypeHint {
+    fn to_json(&self) -> Json {
+        with_region(&self.region, vec![("type", self.ty.to_json())])
+    }
+}
+
+
+impl ToJson for InactiveRegion {
+    fn to_json(&self) -> Json {
+        with_region(&self.region, vec![("predicate", self.predicate.to_json())])
+    }
+}
+
+
+impl ToJson for Diagnostic {
+    fn to_json(&self) -> Json {
+        let fields = vec![
+            ("level", self.level.to_json()),
+            ("message", self.message.to_json()),
+            ("code", self.code.to_json())
+        ];
+        match self.region {
+            Some(ref region) => with_region(region, fields),
+            None => {
+                let mut fields = fields;
+                fields.extend(vec![("file", Json::Null), ("start", Json::Null), ("end", Json::Null)]);
+                object(fields)
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use serialize::json::{Json, ToJson};
+    use compiler_api::CrateType;
+    use explorer::CrateJob;
+    use highlight::HighlightedToken;
+    use index::{Index, SourceFile, index_crate};
+    use navigation::{ActiveRegion, Definition, ExternalItem, Region, Target};
+    use super::index_to_json;
+
+    fn region(start: usize, end: usize) -> Region {
+        Region { filename: "main.rs".to_string(), start: start, end: end }
+    }
+
+    #[test]
+    fn test_index_to_json() {
+        let file = SourceFile {
+            name: "main.rs".to_string(),
+            src: "fn main() { main() }\n".to_string(),
+            tokens: vec![HighlightedToken { start: 0, end: 1, class: Some("keyword") }]
+        };
+        let index = Index {
+            definitions: vec![Definition {
+                id: 1, region: region(3, 6), path: Some("main".to_string()), kind: "fn"
+            }],
+            active_regions: vec![
+                ActiveRegion { target: Target::Definition("main.rs".to_string(), 1), region: region(12, 15) },
+                ActiveRegion {
+                    target: Target::External(ExternalItem {
+                        krate: "std".to_string(), path: vec!["vec".to_string(), "Vec".to_string()], kind: "struct",
+                        parent: None
+                    }),
+                    region: region(0, 1)
+                }
+            ],
+            ..Index::new(vec![file])
+        };
+
+        let json = index_to_json("main", &index);
+        assert_eq!(json.find("crate"), Some(&"main".to_json()));
+        assert_eq!(json.find_path(&["files"]).unwrap().as_array().unwrap().len(), 1);
+
+        let definition = &json.find("definitions").unwrap().as_array().unwrap()[0];
+        assert_eq!(definition.find("name"), Some(&"main".to_json()));
+        assert_eq!(definition.find("kind"), Some(&"fn".to_json()));
+        assert_eq!(definition.find("end"), Some(&6usize.to_json()));
+
+        let active_regions = json.find("active_regions").unwrap().as_array().unwrap();
+        assert_eq!(active_regions[0].find_path(&["target", "id"]), Some(&1u32.to_json()));
+        assert_eq!(active_regions[1].find_path(&["target", "path"]), Some(&"vec::Vec".to_json()));
+        assert_eq!(json.find("failure"), Some(&Json::Null));
+    }
+
+    #[test]
+    fn test_unreferenced_items() {
+        let src = "pub fn unused() {}\n\nfn used() {}\n\npub fn caller() { used() }\n";
+        let job = CrateJob::fixture("unreferenced_items", CrateType::Rlib, &[("lib.rs", src)]);
+        let json = index_to_json(&job.name, &index_crate(&job).unwrap());
+        fs::remove_dir_all(&job.input).unwrap();
+
+        assert_eq!(json.find("failure"), Some(&Json::Null));
+        let mut names = json.find("definitions").unwrap().as_array().unwrap().iter()
+            .map(|definition| {
+                let name = definition.find("name").and_then(|name| name.as_string()).unwrap();
+                let offset = |key: &str| definition.find(key).and_then(|offset| offset.as_u64()).unwrap() as usize;
+                // definitions span just their names
+                assert_eq!(&src[offset("start")..offset("end") + 1], name);
+                name.to_string()
+            })
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!["caller", "unused", "used"]);
+    }
+}
@@ -25,8 +25,8 @@ pub fn read_tokens(filemap: Rc<FileMap>) -> Vec<Token> {
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Interval {
-    pub lower_bound: usize, // inclusive
-    pub upper_bound: usize, // inclusive
+    pub lower_bound: usize, // inclusive byte offset from the start of the file
+    pub upper_bound: usize, // inclusive byte offset from the start of the file
     filename: String,
 }
 
@@ -72,8 +72,7 @@ impl IntervalToSnippet for FileMap {
     fn interval_to_snippet(&self, interval: &Interval) -> Option<String> {
         match (self.src.clone(), self.name == interval.filename) {
             (Some(src), true) => {
-                let snippet = src[interval.lower_bound .. interval.upper_bound + 1].to_string();
-                Some(escape_shippet(snippet))
+                Some(src[interval.lower_bound .. interval.upper_bound + 1].to_string())
             },
             _ => None
         }
@@ -90,15 +89,6 @@ impl IntervalToSnippet for CodeMap {
 }
 
 
-fn escape_shippet(snippet: String) -> String {
-    match &snippet[..] {
-        "<" => "&lt;".to_string(),
-        ">" => "&gt;".to_string(),
-        _ => snippet
-    }
-}
-
-
 #[cfg(test)]
 mod tests {
     use syntax::codemap::CodeMap;
@@ -126,4 +116,23 @@ mod tests {
 
         assert_eq!(source, SOURCE);
     }
+
+    #[test]
+    fn test_multibyte_source() {
+        const MULTIBYTE: &'static str = "// комментарий\nfn main() { let s = \"🦀\"; }\n";
+        let codemap = CodeMap::new();
+        codemap.new_filemap("first.rs".into(), SOURCE.into());
+        let filemap = codemap.new_filemap("second.rs".into(), MULTIBYTE.into());
+        let tokens = read_tokens(filemap.clone());
+
+        let main = tokens.iter()
+            .find(|token| filemap.interval_to_snippet(&token.interval).unwrap() == "main")
+            .expect("main token");
+        assert_eq!(main.interval.lower_bound, MULTIBYTE.find("main").unwrap());
+
+        let source = tokens.iter()
+            .map(|token| filemap.interval_to_snippet(&token.interval).unwrap())
+            .collect::<Vec<_>>().concat();
+        assert_eq!(source, MULTIBYTE);
+    }
 }
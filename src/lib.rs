@@ -0,0 +1,71 @@
+//! Renders the sources of a crate to html with navigation to definitions,
+//! references, type hints and compiler diagnostics.
+//!
+//! Exploring is split into two steps: a crate is analyzed into an `Index`,
+//! which is then rendered to pages or exported, e.g. as JSON or LSIF.
+//! The pages can also be rendered on request instead, see `server`, or written again
+//! whenever the sources change, see `watch`.
+//!
+//! ```ignore
+//! let config = Config::new(input, output, template);
+//! for job in try!(crate_jobs(&config)) {
+//!     let index = try!(index_crate(&job));
+//!     try!(write_index(&config, &job, &index));
+//! }
+//! ```
+
+#![feature(rustc_private)]
+#![feature(path_ext)]
+#![feature(duration_span)]
+
+
+extern crate getopts;
+extern crate rustc;
+extern crate rustc_driver;
+extern crate rustc_resolve;
+extern crate rustc_trans;
+extern crate serialize;
+extern crate syntax;
+
+
+#[macro_use]
+pub mod log;
+
+mod cfg;
+mod compiler_api;
+mod custom_collections;
+mod dependencies;
+pub mod diagnostics;
+pub mod errors;
+pub mod explorer;
+mod external_links;
+mod file_tree;
+#[cfg(test)]
+mod fixture;
+pub mod highlight;
+mod html;
+pub mod index;
+pub mod json_index;
+mod lexer;
+pub mod lsif;
+mod manifest;
+mod navigation;
+mod output;
+mod path_extensions;
+mod references;
+mod render;
+pub mod server;
+pub mod site;
+pub mod tags;
+pub mod watch;
+
+
+pub use compiler_api::{CompilerOptions, CrateType};
+pub use errors::{Error, Result};
+pub use explorer::{Config, CrateJob, Format, crate_jobs, write_index};
+pub use index::{Index, SourceFile, index_crate};
+pub use manifest::ManifestError;
+pub use navigation::{ActiveRegion, Definition, ExternalItem, InactiveRegion, Region, Target, TypeHint};
+pub use server::serve;
+pub use site::{Site, Stats, write_site};
+pub use watch::watch;
@@ -0,0 +1,104 @@
+//! Messages about the progress, printed to stderr. Stdout is left for the results, e.g. the final summary,
+//! which is printed only from the `Normal` level up too.
+
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use std::time::Duration;
+
+
+macro_rules! log_info {
+    ($($arg:tt)*) => (
+        if $crate::log::enabled($crate::log::Level::Normal) { $crate::log::print(format_args!($($arg)*)) }
+    )
+}
+
+
+macro_rules! log_verbose {
+    ($($arg:tt)*) => (
+        if $crate::log::enabled($crate::log::Level::Verbose) { $crate::log::print(format_args!($($arg)*)) }
+    )
+}
+
+
+macro_rules! log_debug {
+    ($($arg:tt)*) => (
+        if $crate::log::enabled($crate::log::Level::Debug) { $crate::log::print(format_args!($($arg)*)) }
+    )
+}
+
+
+/// Amount of output: messages on stderr, warnings of the compiler and the final summary on stdout.
+/// Errors are reported regardless of it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// Errors only.
+    Quiet,
+    /// Warnings, including the ones of the compiler, and the final summary.
+    Normal,
+    /// Progress of every crate and timings of the phases.
+    Verbose,
+    /// Everything, e.g. every written file and the phases of the compiler.
+    Debug
+}
+
+
+// 0 means the level isn't set and is `Normal`
+static LEVEL: AtomicUsize = ATOMIC_USIZE_INIT;
+
+
+pub fn set_level(level: Level) {
+    LEVEL.store(level as usize + 1, Ordering::Relaxed);
+}
+
+
+pub fn level() -> Level {
+    match LEVEL.load(Ordering::Relaxed) {
+        0 | 2 => Level::Normal,
+        1 => Level::Quiet,
+        3 => Level::Verbose,
+        _ => Level::Debug
+    }
+}
+
+
+pub fn enabled(level: Level) -> bool {
+    level != Level::Quiet && level <= self::level()
+}
+
+
+/// Used by the macros, a message is lost if stderr is closed.
+pub fn print(args: fmt::Arguments) {
+    let _ = writeln!(io::stderr(), "{}", args);
+}
+
+
+/// Runs `f` and reports how long it took at the verbose level.
+pub fn timed<T, F: FnOnce() -> T>(what: &str, f: F) -> T {
+    let mut result = None;
+    let duration = {
+        let result = &mut result;
+        Duration::span(move || *result = Some(f()))
+    };
+    log_verbose!("{}: {}", what, format_duration(duration));
+    result.expect("timed result")
+}
+
+
+/// `1.042s`
+pub fn format_duration(duration: Duration) -> String {
+    format!("{}.{:03}s", duration.as_secs(), duration.subsec_nanos() / 1000000)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::format_duration;
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::new(1, 42000000)), "1.042s");
+        assert_eq!(format_duration(Duration::new(0, 999999)), "0.000s");
+    }
+}
@@ -0,0 +1,319 @@
+//! Export of the navigation graph as LSIF (https://microsoft.github.io/language-server-protocol/specifications/lsif/0.4.0/specification/),
+//! which code-intelligence viewers can load.
+//!
+//! `dump.lsif` is written to the output directory of the crate, one vertex or edge per line.
+//! Every definition gets a result set with definition, references and hover results, ranges
+//! of its references point to the result set. Ranges of definitions cover their names only.
+//! References with a known type get their own hover with it. References to items of other
+//! crates are skipped.
+
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use serialize::json::{Json, ToJson};
+use errors::Result;
+use explorer::CrateJob;
+use index::Index;
+use json_index::object;
+use navigation::{Region, Target};
+use output::OutputWriter;
+use site::Stats;
+
+
+pub const LSIF_DUMP: &'static str = "dump.lsif";
+const LSIF_VERSION: &'static str = "0.4.3";
+
+
+pub fn write_lsif(job: &CrateJob, index: &Index) -> Result<Stats> {
+    let dump = lsif_dump(&absolute(&job.input), &absolute(Path::new(".")), index);
+    let mut writer = OutputWriter::new(job.output.clone());
+    try!(writer.write(Path::new(LSIF_DUMP), &dump.to_string()));
+    Ok(Stats {
+        files: writer.written_count(),
+        definitions: index.definitions.len(),
+        links: dump.references,
+        unapplied_wrappers: 0
+    })
+}
+
+
+/// Vertices and edges of the dump.
+pub struct Dump {
+    pub elements: Vec<Json>,
+    pub references: usize,
+    last_id: u64
+}
+
+
+impl Dump {
+    fn new() -> Dump {
+        Dump { elements: Vec::new(), references: 0, last_id: 0 }
+    }
+
+    fn vertex(&mut self, label: &str, fields: Vec<(&str, Json)>) -> u64 {
+        self.element("vertex", label, fields)
+    }
+
+    fn edge(&mut self, label: &str, out_v: u64, in_vs: Vec<u64>, fields: Vec<(&str, Json)>) -> u64 {
+        let mut all = vec![("outV", out_v.to_json())];
+        if label == "next" || label.starts_with("textDocument/") {
+            all.push(("inV", in_vs[0].to_json()));
+        } else {
+            all.push(("inVs", in_vs.to_json()));
+        }
+        all.extend(fields.into_iter());
+        self.element("edge", label, all)
+    }
+
+    fn element(&mut self, element_type: &str, label: &str, fields: Vec<(&str, Json)>) -> u64 {
+        self.last_id += 1;
+        let mut all = vec![
+            ("id", self.last_id.to_json()),
+            ("type", element_type.to_json()),
+            ("label", label.to_json())
+        ];
+        all.extend(fields.into_iter());
+        self.elements.push(object(all));
+        self.last_id
+    }
+}
+
+
+impl fmt::Display for Dump {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for element in self.elements.iter() {
+            try!(writeln!(f, "{}", element));
+        }
+        Ok(())
+    }
+}
+
+
+/// Relative file names are resolved against `base`, the directory the compiler was run in.
+pub fn lsif_dump(project_root: &Path, base: &Path, index: &Index) -> Dump {
+    let mut dump = Dump::new();
+    dump.vertex("metaData", vec![
+        ("version", LSIF_VERSION.to_json()),
+        ("projectRoot", file_uri(project_root).to_json()),
+        ("positionEncoding", "utf-16".to_json()),
+        ("toolInfo", object(vec![("name", "code_explorer".to_json())]))
+    ]);
+    let project = dump.vertex("project", vec![("kind", "rust".to_json())]);
+
+    let mut documents = BTreeMap::new();
+    let mut lines = HashMap::new();
+    for file in index.files.iter() {
+        let uri = file_uri(&base.join(&file.name));
+        let document = dump.vertex("document", vec![("uri", uri.to_json()), ("languageId", "rust".to_json())]);
+        documents.insert(file.name.clone(), document);
+        lines.insert(file.name.clone(), LineIndex::new(&file.src));
+    }
+    let document_ids = documents.values().cloned().collect();
+    dump.edge("contains", project, document_ids, Vec::new());
+
+    let mut references = HashMap::new();
+    for active_region in index.active_regions.iter() {
+        if let Target::Definition(_, id) = active_region.target {
+            references.entry(id).or_insert_with(|| Vec::new()).push(active_region.region.clone());
+        }
+    }
+    let types = index.type_hints.iter()
+        .map(|hint| (&hint.region, &hint.ty))
+        .collect::<HashMap<_, _>>();
+
+    let mut ranges_by_document = BTreeMap::new();
+    for definition in index.definitions.iter() {
+        let filename = &definition.region.filename;
+        let (document, line_index) = match (documents.get(filename), lines.get(filename)) {
+            (Some(&document), Some(line_index)) => (document, line_index),
+            _ => continue
+        };
+
+        let result_set = dump.vertex("resultSet", Vec::new());
+        let range = dump.vertex("range", line_index.range(&definition.region));
+        dump.edge("next", range, vec![result_set], Vec::new());
+        ranges_by_document.entry(document).or_insert_with(|| Vec::new()).push(range);
+
+        let src = index.file(filename).map_or("", |file| &file.src[..]);
+        let name = definition.path.as_ref().map_or(definition.name(src), |path| &path[..]);
+        let mut text = format!("{} {}", definition.kind, name);
+        if let Some(ty) = types.get(&&definition.region) {
+            text.push_str(&format!(": {}", ty));
+        }
+        let hover_result = hover(&mut dump, &text);
+        dump.edge("textDocument/hover", result_set, vec![hover_result], Vec::new());
+
+        let definition_result = dump.vertex("definitionResult", Vec::new());
+        dump.edge("textDocument/definition", result_set, vec![definition_result], Vec::new());
+        dump.edge("item", definition_result, vec![range], vec![("document", document.to_json())]);
+
+        let reference_result = dump.vertex("referenceResult", Vec::new());
+        dump.edge("textDocument/references", result_set, vec![reference_result], Vec::new());
+        dump.edge("item", reference_result, vec![range], vec![
+            ("document", document.to_json()), ("property", "definitions".to_json())
+        ]);
+
+        let mut reference_ranges = BTreeMap::new();
+        for region in references.get(&definition.id).map_or(&[][..], |regions| &regions[..]) {
+            if let (Some(&document), Some(line_index)) = (documents.get(&region.filename), lines.get(&region.filename)) {
+                let range = dump.vertex("range", line_index.range(region));
+                dump.edge("next", range, vec![result_set], Vec::new());
+                if let Some(ty) = types.get(&region) {
+                    let hover_result = hover(&mut dump, &format!("{}: {}", name, ty));
+                    dump.edge("textDocument/hover", range, vec![hover_result], Vec::new());
+                }
+                ranges_by_document.entry(document).or_insert_with(|| Vec::new()).push(range);
+                reference_ranges.entry(document).or_insert_with(|| Vec::new()).push(range);
+                dump.references += 1;
+            }
+        }
+        for (document, ranges) in reference_ranges {
+            dump.edge("item", reference_result, ranges, vec![
+                ("document", document.to_json()), ("property", "references".to_json())
+            ]);
+        }
+    }
+
+    for (document, ranges) in ranges_by_document {
+        dump.edge("contains", document, ranges, Vec::new());
+    }
+    dump
+}
+
+
+fn hover(dump: &mut Dump, text: &str) -> u64 {
+    dump.vertex("hoverResult", vec![
+        ("result", object(vec![("contents", object(vec![
+            ("language", "rust".to_json()),
+            ("value", text.to_json())
+        ]))]))
+    ])
+}
+
+
+/// Converts byte offsets to LSIF positions: zero-based lines and utf-16 characters.
+struct LineIndex<'a> {
+    src: &'a str,
+    line_starts: Vec<usize>
+}
+
+
+impl<'a> LineIndex<'a> {
+    fn new(src: &'a str) -> LineIndex<'a> {
+        let mut line_starts = vec![0];
+        line_starts.extend(src.char_indices().filter(|&(_, ch)| ch == '\n').map(|(index, _)| index + 1));
+        LineIndex { src: src, line_starts: line_starts }
+    }
+
+    fn position(&self, offset: usize) -> Json {
+        let offset = if offset > self.src.len() { self.src.len() } else { offset };
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next_line) => next_line - 1
+        };
+        let character = self.src[self.line_starts[line]..offset].chars()
+            .fold(0, |count, ch| count + ch.len_utf16());
+        object(vec![("line", line.to_json()), ("character", character.to_json())])
+    }
+
+    fn range(&self, region: &Region) -> Vec<(&'static str, Json)> {
+        // LSIF ranges are end exclusive
+        vec![("start", self.position(region.start)), ("end", self.position(region.end + 1))]
+    }
+}
+
+
+fn absolute(path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        env::current_dir().map(|dir| dir.join(path)).unwrap_or(path.to_path_buf())
+    }
+}
+
+
+fn file_uri(path: &Path) -> String {
+    let mut uri = "file://".to_string();
+    for &byte in path.to_string_lossy().as_bytes() {
+        match byte {
+            b'A'...b'Z' | b'a'...b'z' | b'0'...b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => uri.push(byte as char),
+            _ => uri.push_str(&format!("%{:02X}", byte))
+        }
+    }
+    uri
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use serialize::json::ToJson;
+    use highlight::HighlightedToken;
+    use index::{Index, SourceFile};
+    use navigation::{ActiveRegion, Definition, Region, Target, TypeHint};
+    use super::{LineIndex, file_uri, lsif_dump};
+
+    fn region(start: usize, end: usize) -> Region {
+        Region { filename: "main.rs".to_string(), start: start, end: end }
+    }
+
+    #[test]
+    fn test_positions() {
+        let line_index = LineIndex::new("fn main() {\n    let ёж = 1;\n}\n");
+        let range = line_index.range(&region(25, 25));
+        assert_eq!(range[0].1.find("line"), Some(&1usize.to_json()));
+        // `ё` and `ж` take two bytes but one utf-16 unit each
+        assert_eq!(range[0].1.find("character"), Some(&11usize.to_json()));
+        assert_eq!(range[1].1.find("character"), Some(&12usize.to_json()));
+    }
+
+    #[test]
+    fn test_dump() {
+        let file = SourceFile {
+            name: "main.rs".to_string(),
+            src: "fn main() { main() }\n".to_string(),
+            tokens: vec![HighlightedToken { start: 0, end: 1, class: Some("keyword") }]
+        };
+        let index = Index {
+            definitions: vec![Definition {
+                id: 1, region: region(3, 6), path: Some("main".to_string()), kind: "fn"
+            }],
+            active_regions: vec![
+                ActiveRegion { target: Target::Definition("main.rs".to_string(), 1), region: region(12, 15) }
+            ],
+            type_hints: vec![TypeHint { region: region(12, 15), ty: "fn() {main}".to_string() }],
+            ..Index::new(vec![file])
+        };
+
+        let dump = lsif_dump(Path::new("/project"), Path::new("/project"), &index);
+        let labels = dump.elements.iter()
+            .map(|element| element.find("label").and_then(|label| label.as_string()).unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(labels.iter().filter(|label| *label == "range").count(), 2);
+        assert_eq!(labels.iter().filter(|label| *label == "textDocument/references").count(), 1);
+        assert_eq!(dump.references, 1);
+
+        let document = dump.elements.iter()
+            .find(|element| element.find("label") == Some(&"document".to_json()))
+            .unwrap();
+        assert_eq!(document.find("uri"), Some(&"file:///project/main.rs".to_json()));
+
+        let hovers = dump.elements.iter()
+            .filter(|element| element.find("label") == Some(&"hoverResult".to_json()))
+            .map(|hover| hover.find_path(&["result", "contents", "value"]).unwrap().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(hovers, vec!["fn main".to_json(), "main: fn() {main}".to_json()]);
+
+        let definition_range = dump.elements.iter()
+            .find(|element| element.find("label") == Some(&"range".to_json()))
+            .unwrap();
+        assert_eq!(definition_range.find_path(&["start", "character"]), Some(&3usize.to_json()));
+        assert_eq!(definition_range.find_path(&["end", "character"]), Some(&7usize.to_json()));
+    }
+
+    #[test]
+    fn test_file_uri() {
+        assert_eq!(file_uri(Path::new("/my project/src/ёж#1.rs")), "file:///my%20project/src/%D1%91%D0%B6%231.rs");
+    }
+}
@@ -1,76 +1,166 @@
-#![feature(rustc_private)]
-#![feature(path_ext)]
-
-
+extern crate code_explorer;
 extern crate getopts;
-extern crate rustc;
-extern crate rustc_driver;
-extern crate rustc_resolve;
-extern crate rustc_trans;
-extern crate syntax;
-
 
-mod compiler_api;
-mod custom_collections;
-mod navigation;
-mod html;
-mod lexer;
-mod render;
 
-
-use std::path::Path;
+use std::fmt::Display;
+use std::io::{self, Write};
 use std::path::PathBuf;
-use compiler_api::{
-    CtxtArenas, Forest, build_session, get_main_file_path, parse_and_expand,
-    assign_node_ids_and_map, analyze
-};
-use syntax::codemap::CodeMap;
-use std::fs::File;
-use std::io::Read;
-use navigation::{collect_mappings, Definition, ActiveRegion};
-use render::{Chunk, Wrapper, apply_wrappers};
-use html::tags::{Span, A};
-use std::collections::HashMap;
-use path_extensions::PathExtensions;
-use std::rc::Rc;
-use syntax::codemap::{FileMap};
-use std::hash::Hash;
+use std::process;
+use code_explorer::{Config, Result, Stats, crate_jobs, index_crate, serve, write_index};
+use code_explorer::watch::{explored_files, watch};
+use code_explorer::log::{self, Level};
 
 
 mod options {
     use getopts::{Options};
     use std::path::PathBuf;
     use std::env::Args;
+    use code_explorer::{Config, Format};
+    use code_explorer::log::Level;
     use self::errors::Error;
 
 
-    pub fn parse(args: Args) -> OptionsResult<Opts> {
+    pub enum Command {
+        /// Write the pages or the index of the crates.
+        Explore,
+        /// Write them again whenever the sources or the template change.
+        Watch,
+        /// `serve` subcommand: serve the pages on the port without writing them.
+        Serve(u16)
+    }
+
+
+    pub fn parse(args: Args) -> OptionsResult<(Config, Level, Command)> {
+        let mut args = args.skip(1).collect::<Vec<_>>();
+        let serving = args.first().map_or(false, |arg| arg == "serve");
+        if serving {
+            args.remove(0);
+        }
+
         let parser = create_options_parser();
-        let opts = try!(parser.parse(args.skip(1)));
+        let opts = try!(parser.parse(args));
+
+        let command = if serving {
+            let port = match opts.opt_str("port") {
+                Some(port) => try!(port.parse().map_err(|_| Error::from("invalid port"))),
+                None => DEFAULT_PORT
+            };
+            if opts.opt_present("watch") {
+                return Err(Error::from("--watch isn't supported by `serve`"));
+            }
+            Command::Serve(port)
+        } else if opts.opt_present("watch") {
+            Command::Watch
+        } else {
+            Command::Explore
+        };
+        let output = match opts.opt_str("o") {
+            Some(output) => PathBuf::from(output),
+            None if serving => PathBuf::new(),
+            None => return Err(Error::from("Required option 'o' missing."))
+        };
+
+        let mut extern_explored = Vec::new();
+        for value in opts.opt_strs("extern-explored") {
+            extern_explored.push(try!(parse_key_value(&value)));
+        }
+
+        let level = match (opts.opt_present("q"), opts.opt_count("v")) {
+            (true, _) => Level::Quiet,
+            (false, 0) => Level::Normal,
+            (false, 1) => Level::Verbose,
+            _ => Level::Debug
+        };
 
-        Ok(Opts {
+        let format = match opts.opt_str("format") {
+            Some(name) => try!(Format::from_name(&name).ok_or(Error::from("unknown format"))),
+            None => Format::Html
+        };
+
+        let config = Config {
             input: PathBuf::from(opts.opt_str("i").unwrap()),
-            output: PathBuf::from(opts.opt_str("o").unwrap()),
-            template: PathBuf::from(opts.opt_str("t").unwrap())
-        })
+            output: output,
+            template: opts.opt_str("t").map(PathBuf::from),
+            format: format,
+            extern_url: opts.opt_str("extern-url"),
+            extern_explored: extern_explored,
+            clean: opts.opt_present("clean"),
+            sysroot: opts.opt_str("sysroot").map(PathBuf::from),
+            rustc_args: opts.opt_strs("rustc-arg"),
+            features: opts.opt_strs("features").iter()
+                .flat_map(|x| x.split(|ch: char| ch == ',' || ch == ' '))
+                .filter(|x| x.len() > 0)
+                .map(|x| x.to_string())
+                .collect(),
+            all_features: opts.opt_present("all-features"),
+            no_default_features: opts.opt_present("no-default-features"),
+            cfgs: opts.opt_strs("cfg"),
+            deps: opts.opt_strs("deps").into_iter().map(PathBuf::from).collect()
+        };
+        Ok((config, level, command))
+    }
+
+
+    fn parse_key_value(value: &str) -> OptionsResult<(String, String)> {
+        let mut parts = value.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some(key), Some(value)) if key.len() > 0 => Ok((key.to_string(), value.to_string())),
+            _ => Err(Error::from("expected value in form KEY=VALUE"))
+        }
     }
 
 
     pub type OptionsResult<T> = Result<T, Error>;
 
 
-    pub struct Opts {
-        pub input: PathBuf,
-        pub output: PathBuf,
-        pub template: PathBuf
-    }
+    const DEFAULT_PORT: u16 = 8000;
 
 
     fn create_options_parser() -> Options {
         let mut opts = Options::new();
-        opts.reqopt("i", "in", "", "DIR");
-        opts.reqopt("o", "out", "", "DIR");
-        opts.reqopt("t", "template", "", "FILE");
+        opts.reqopt("i", "in", "crate directory with Cargo.toml, or directory with main.rs or lib.rs", "DIR");
+        opts.optopt("o", "out", "output directory, not used by `serve`", "DIR");
+        opts.optopt("t", "template", "template of the pages, the built-in one by default", "FILE");
+        opts.optopt(
+            "", "format",
+            "html (default), json, lsif, ctags or etags, see the docs of `json_index` for the JSON schema",
+            "FORMAT"
+        );
+        opts.optopt(
+            "", "extern-url",
+            "link items of other crates to PATTERN, supports {crate}, {path}, {dir}, {name}, {kind} and {page} \
+             placeholders, e.g. file:///doc/{crate}/{page}",
+            "PATTERN"
+        );
+        opts.optmulti(
+            "", "extern-explored",
+            "link items of CRATE to its explored sources placed at URL",
+            "CRATE=URL"
+        );
+        opts.optflag("", "clean", "remove .html files left in the output directory by previous runs");
+        opts.optopt("", "sysroot", "sysroot of the toolchain, by default the one of `rustc --print sysroot`", "DIR");
+        opts.optmulti(
+            "", "rustc-arg",
+            "pass ARG to rustc as is, e.g. --rustc-arg=--cfg --rustc-arg=unix (may be repeated)",
+            "ARG"
+        );
+        opts.optmulti("", "features", "space or comma separated list of features to enable", "FEATURES");
+        opts.optflag("", "all-features", "enable all features of the package, including optional dependencies");
+        opts.optflag("", "no-default-features", "don't enable the `default` feature");
+        opts.optmulti("", "cfg", "configure the compilation environment, e.g. --cfg unix or --cfg key=value", "SPEC");
+        opts.optmulti(
+            "", "deps",
+            "directory with built dependencies, e.g. target/debug/deps (may be repeated); \
+             by default target/debug/deps and target/debug of the cargo project",
+            "DIR"
+        );
+        opts.optflag(
+            "", "watch",
+            "explore again when the sources, Cargo.toml or the template change, only changed files are rewritten"
+        );
+        opts.optopt("", "port", "port for `serve` to listen on, 8000 by default", "PORT");
+        opts.optflag("q", "quiet", "don't print anything except of errors");
+        opts.optflagmulti("v", "verbose", "print progress and timings of the phases, -vv for debug output");
         opts.optflag("h", "help", "print this help menu");
         opts
     }
@@ -107,197 +197,69 @@ mod options {
 
 
 fn main() {
-    let options = match options::parse(std::env::args()) {
+    let (config, level, command) = match options::parse(std::env::args()) {
         Ok(options) => options,
-        Err(err) => { println!("{}", err); return; }
+        Err(err) => exit_with_error(&err)
     };
-
-    println!("args");
-    let (source_path, crate_type) =
-        get_main_file_path(&options.input).expect("Can't find main file.");
-
-    let sess = build_session(source_path.clone(), crate_type);
-    let (id, expanded_crate) = parse_and_expand(&sess, &source_path).unwrap();
-
-    println!("parse");
-    let mut forest = Forest::new(expanded_crate);
-    let arenas = CtxtArenas::new();
-    let map = assign_node_ids_and_map(&sess, &mut forest);
-    println!("assign node ids");
-    let analysis = analyze(sess, id, map, &arenas);
-    println!("analyze");
-
-    let (active_regions, definitions) = collect_mappings(&analysis);
-    println!("collect");
-
-    let def_wrappers = definitions.into_iter().map(|x| (x.region.filename.clone(), x.to_wrapper()));
-    let active_wrappers = active_regions.into_iter().map(|x| (x.region.filename.clone(), x.to_wrapper()));
-
-    let mut wrappers_by_filename = HashMap::new();
-    for (filename, wrapper) in def_wrappers.chain(active_wrappers) {
-        let mut wrappers = wrappers_by_filename.entry(filename).or_insert_with(|| Vec::new());
-        wrappers.push(wrapper);
-    }
-
-
-    let codemap = analysis.ty_cx.sess.codemap();
-    for filemap in filemaps(codemap) {
-        println!("\n\n>>>>>>>>>>>>>>>>>>>>>>>>>>>>> FILE: {}", &filemap.name);
-        let tokens = lexer::read_tokens(filemap.clone());
-        let wrappers = wrappers_by_filename.remove(&filemap.name).unwrap_or_else(|| Vec::new());
-        let result = apply_wrappers(&filemap, tokens, wrappers);
-        let full = render_code(&options.template, result);
-        let mut result_path = options.output.join(
-            &PathBuf::from(&filemap.name).relative_to(&options.input).unwrap()
-        );
-        result_path.set_extension("html");
-        write_file(&result_path, &full)
-    }
-}
-
-
-fn render_code<T: AsRef<str>>(template_path: &Path, data: T) -> String {
-    fn render_lines(lines_count: usize) -> String {
-        let mut lines_buf = String::new();
-        for line in (1..lines_count + 1) {
-            lines_buf.push_str(&format!("<li>{}</li>", line));
-        }
-        lines_buf
-    }
-
-    let lines = render_lines(data.as_ref().lines().count());
-    let mut variables = HashMap::new();
-    variables.insert("{{code}}", data.as_ref());
-    variables.insert("{{lines}}", &lines[..]);
-
-    render_template(template_path, &variables)
-}
-
-
-fn render_template<K, V>(template_path: &Path, variables: &HashMap<K, V>) -> String where K: AsRef<str> + Hash + Eq, V: AsRef<str> {
-    variables.iter().fold(
-        read_file(template_path),
-        |template, (key, value)| template.replace(key.as_ref(), value.as_ref())
-    )
-}
-
-
-fn read_file(path: &Path) -> String {
-    use std::fs::File;
-
-    let mut file = File::open(path).unwrap();
-    let mut buf = String::new();
-    file.read_to_string(&mut buf).unwrap();
-    buf
-}
-
-
-fn write_file<T: AsRef<str>>(path: &Path, data: &T) {
-    use std::io::Write;
-    println!("write {:?}", path);
-    let mut f = File::create(path).ok().expect("create fil");
-    f.write_all(data.as_ref().as_bytes()).ok().expect("write file");
-}
-
-
-fn filemaps(codemap: &CodeMap) -> Vec<Rc<FileMap>> {
-    let mut filemaps = Vec::new();
-    for fm in &*codemap.files.borrow() {
-        if fm.is_real_file() && fm.src != None {
-            filemaps.push(fm.clone());
+    log::set_level(level);
+
+    let result = match command {
+        options::Command::Explore => explore_and_report(&config).map(|_| ()),
+        options::Command::Serve(port) => serve(&config, port),
+        options::Command::Watch => {
+            // a failed run doesn't stop watching, the sources may be fixed by the next change,
+            // the files of the last successful run are watched meanwhile
+            let mut explored = Vec::new();
+            let mut report = || {
+                match explore_and_report(&config) {
+                    Ok(files) => explored = files,
+                    Err(err) => print_error(&err)
+                }
+                explored.clone()
+            };
+            let explored = report();
+            watch(&config, explored, report);
+            Ok(())
         }
+    };
+    if let Err(err) = result {
+        exit_with_error(&err);
     }
-    filemaps
 }
 
 
-trait ToWrapper {
-    fn to_wrapper(&self) -> Wrapper;
+fn print_error<E: Display>(err: &E) {
+    // nothing is left to report the error to if stderr is closed
+    let _ = writeln!(io::stderr(), "error: {}", err);
 }
 
 
-impl ToWrapper for Definition {
-    fn to_wrapper(&self) -> Wrapper {
-        let tag = Span::new()
-            .add_class("definition")
-            .add_id(format!("def-{}", self.id))
-            .set_name(format!("def-{}", self.id));
-        Wrapper::new(
-            Chunk::new(self.region.start, tag.render_open()),
-            Chunk::new(self.region.end, tag.render_close())
-        )
-    }
+fn exit_with_error<E: Display>(err: &E) -> ! {
+    print_error(err);
+    process::exit(1);
 }
 
 
-
-impl ToWrapper for ActiveRegion {
-    fn to_wrapper(&self) -> Wrapper {
-        let from_path = PathBuf::from(self.region.filename.clone());
-        let def_path = PathBuf::from(self.def.0.clone());
-        let mut path_to_def = def_path.relative_to(&from_path).unwrap();
-        path_to_def.set_extension("html");
-        let mut path_as_str = path_to_def.to_str().unwrap();
-        if path_as_str.len() > 0 {
-           path_as_str = &path_as_str[1..path_as_str.len()]
-        }
-        let tag = A::new().add_class("active-region").set_href(format!("{}#def-{}", path_as_str, self.def.1));
-        Wrapper::new(
-            Chunk::new(self.region.start, tag.render_open()),
-            Chunk::new(self.region.end, tag.render_close())
-        )
+/// Returns the files the crates were explored from.
+fn explore_and_report(config: &Config) -> Result<Vec<PathBuf>> {
+    let (stats, explored) = try!(log::timed("total", || explore(config)));
+    if log::enabled(Level::Normal) {
+        println!(
+            "{} files written, {} definitions, {} links, {} wrappers not applied",
+            stats.files, stats.definitions, stats.links, stats.unapplied_wrappers
+        );
     }
+    Ok(explored)
 }
 
 
-mod path_extensions {
-    use std::path::{Path, PathBuf, Component};
-
-
-    pub trait PathExtensions {
-        fn relative_to<P: ?Sized + AsRef<Path>>(&self, &P) -> Option<PathBuf>;
-    }
-
-
-    impl PathExtensions for Path {
-        fn relative_to<P: ?Sized + AsRef<Path>>(&self, base: &P) -> Option<PathBuf> {
-            let base = base.as_ref();
-
-            if self.is_absolute() != base.is_absolute() {
-                if self.is_absolute() {
-                    Some(PathBuf::from(self))
-                } else {
-                    None
-                }
-            } else {
-                let mut ita = self.components();
-                let mut itb = base.components();
-                let mut comps: Vec<Component> = vec![];
-                loop {
-                    match (ita.next(), itb.next()) {
-                        (None, None) => break,
-                        (Some(a), None) => {
-                            comps.push(a);
-                            comps.extend(ita.by_ref());
-                            break;
-                        }
-                        (None, _) => comps.push(Component::ParentDir),
-                        (Some(a), Some(b)) if comps.is_empty() && a == b => (),
-                        (Some(a), Some(b)) if b == Component::CurDir => comps.push(a),
-                        (Some(_), Some(b)) if b == Component::ParentDir => return None,
-                        (Some(a), Some(_)) => {
-                            comps.push(Component::ParentDir);
-                            for _ in itb {
-                                comps.push(Component::ParentDir);
-                            }
-                            comps.push(a);
-                            comps.extend(ita.by_ref());
-                            break;
-                        }
-                    }
-                }
-                Some(comps.iter().map(|c| c.as_os_str()).collect())
-            }
-        }
+fn explore(config: &Config) -> Result<(Stats, Vec<PathBuf>)> {
+    let mut stats = Stats::default();
+    let mut explored = Vec::new();
+    for job in try!(crate_jobs(config)) {
+        let index = try!(index_crate(&job));
+        stats.add(&try!(write_index(config, &job, &index)));
+        explored.extend(explored_files(&job, &index));
     }
+    Ok((stats, explored))
 }
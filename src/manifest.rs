@@ -0,0 +1,425 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use serialize::json::Json;
+
+
+pub const MANIFEST: &'static str = "Cargo.toml";
+
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TargetKind {
+    Lib,
+    Bin,
+    Example,
+    Test,
+    Bench
+}
+
+
+impl TargetKind {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            TargetKind::Lib => "lib",
+            TargetKind::Bin => "bin",
+            TargetKind::Example => "example",
+            TargetKind::Test => "test",
+            TargetKind::Bench => "bench"
+        }
+    }
+
+    /// Targets are explored in this order: the library, binaries, examples, tests and benches.
+    fn order(&self) -> u8 {
+        match *self {
+            TargetKind::Lib => 0,
+            TargetKind::Bin => 1,
+            TargetKind::Example => 2,
+            TargetKind::Test => 3,
+            TargetKind::Bench => 4
+        }
+    }
+}
+
+
+/// Crate built from a single root file of the package, e.g. `src/bin/tool.rs`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CrateTarget {
+    pub kind: TargetKind,
+    pub name: String,
+    pub src_path: PathBuf,
+    /// `crate-type` of the target, e.g. `["cdylib", "rlib"]` or `["proc-macro"]`.
+    pub crate_types: Vec<String>
+}
+
+
+#[derive(Debug)]
+pub struct Package {
+    pub name: String,
+    pub root: PathBuf,
+    pub targets: Vec<CrateTarget>,
+    /// `[features]` table and the implicit features of optional dependencies.
+    pub features: BTreeMap<String, Vec<String>>
+}
+
+
+impl Package {
+    /// Names of enabled features including ones enabled by other features.
+    pub fn resolve_features(&self, requested: &[String], all: bool, no_default: bool) -> Vec<String> {
+        let mut pending = requested.to_vec();
+        if all {
+            pending.extend(self.features.keys().cloned());
+        }
+        if !no_default && self.features.contains_key("default") {
+            pending.push("default".to_string());
+        }
+
+        let mut enabled = BTreeSet::new();
+        while let Some(feature) = pending.pop() {
+            // `dependency/feature` is a feature of a dependency, `dep:dependency` enables it without a feature
+            if feature.contains('/') || feature.starts_with("dep:") || enabled.contains(&feature) {
+                continue;
+            }
+            if let Some(implied) = self.features.get(&feature) {
+                pending.extend(implied.iter().cloned());
+            }
+            enabled.insert(feature);
+        }
+        enabled.into_iter().collect()
+    }
+}
+
+
+pub fn is_cargo_project(dir: &Path) -> bool {
+    fs::metadata(dir.join(MANIFEST)).map(|metadata| metadata.is_file()).unwrap_or(false)
+}
+
+
+/// Packages of the manifest in `dir` and of its workspace members, as cargo sees them.
+/// Targets are the ones cargo builds, including the implicit ones like `src/bin/<name>/main.rs`.
+pub fn discover(dir: &Path) -> ManifestResult<Vec<Package>> {
+    // cargo reports canonical paths
+    let dir = fs::canonicalize(dir).unwrap_or(dir.to_path_buf());
+    let manifest_path = dir.join(MANIFEST);
+    let metadata = try!(read_metadata(&manifest_path, false));
+    load_packages(&dir, &manifest_path, &metadata)
+}
+
+
+/// Roots of the packages of the libraries the manifest in `dir` depends on, by crate name,
+/// as resolved by cargo, i.e. in the versions of the lock file.
+pub fn resolved_libraries(dir: &Path) -> ManifestResult<BTreeMap<String, PathBuf>> {
+    let manifest_path = dir.join(MANIFEST);
+    let metadata = try!(read_metadata(&manifest_path, true));
+    load_libraries(&manifest_path, &metadata)
+}
+
+
+/// Output of `cargo metadata`, the manifest is left to cargo to read.
+/// With `dependencies` the packages of the whole dependency graph are listed.
+fn read_metadata(manifest_path: &Path, dependencies: bool) -> ManifestResult<Json> {
+    let cargo = env::var("CARGO").unwrap_or("cargo".to_string());
+    let mut command = Command::new(&cargo);
+    command.arg("metadata").arg("--format-version").arg("1").arg("--manifest-path").arg(manifest_path);
+    if !dependencies {
+        command.arg("--no-deps");
+    }
+    let output = try!(command.output()
+        .map_err(|err| ManifestError::new(manifest_path, format!("can't run {}: {}", cargo, err))));
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(ManifestError::new(manifest_path, stderr.trim().to_string()));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Json::from_str(&stdout).map_err(|err| ManifestError::new(manifest_path, format!("{:?}", err)))
+}
+
+
+/// Packages of the metadata placed in `dir` or under it. The metadata of a workspace member
+/// lists all the workspace, but only the member itself was asked for.
+fn load_packages(dir: &Path, manifest_path: &Path, metadata: &Json) -> ManifestResult<Vec<Package>> {
+    let mut packages = Vec::new();
+    for package in try!(field(manifest_path, metadata, "packages", Json::as_array)) {
+        let package = try!(load_package(manifest_path, package));
+        if package.root.starts_with(dir) {
+            packages.push(package);
+        }
+    }
+    packages.sort_by(|a, b| a.root.cmp(&b.root));
+    Ok(packages)
+}
+
+
+/// Libraries resolved in more than one version are left out: their crate names are the same,
+/// so it's unknown which one is meant.
+fn load_libraries(manifest_path: &Path, metadata: &Json) -> ManifestResult<BTreeMap<String, PathBuf>> {
+    let mut libraries = BTreeMap::new();
+    let mut ambiguous = BTreeSet::new();
+    for package in try!(field(manifest_path, metadata, "packages", Json::as_array)) {
+        let package = try!(load_package(manifest_path, package));
+        for target in package.targets.into_iter().filter(|target| target.kind == TargetKind::Lib) {
+            if libraries.insert(target.name.clone(), package.root.clone()).is_some() {
+                ambiguous.insert(target.name);
+            }
+        }
+    }
+    for name in ambiguous {
+        libraries.remove(&name);
+    }
+    Ok(libraries)
+}
+
+
+fn load_package(manifest_path: &Path, package: &Json) -> ManifestResult<Package> {
+    let name = try!(field(manifest_path, package, "name", Json::as_string)).to_string();
+    let package_manifest = PathBuf::from(try!(field(manifest_path, package, "manifest_path", Json::as_string)));
+    let root = package_manifest.parent().unwrap_or(Path::new("")).to_path_buf();
+
+    let mut targets = Vec::new();
+    for target in try!(field(manifest_path, package, "targets", Json::as_array)) {
+        let kinds = try!(field(manifest_path, target, "kind", Json::as_array));
+        let kind = match kinds.first().and_then(|kind| kind.as_string()) {
+            Some("lib") | Some("rlib") | Some("dylib") | Some("cdylib") | Some("staticlib") | Some("proc-macro") =>
+                TargetKind::Lib,
+            Some("bin") => TargetKind::Bin,
+            Some("example") => TargetKind::Example,
+            Some("test") => TargetKind::Test,
+            Some("bench") => TargetKind::Bench,
+            _ => continue // build scripts
+        };
+        // older cargo reports crate types of libraries as their kinds only
+        let crate_types = target.find("crate_types").map_or_else(
+            || kinds.iter().filter_map(|kind| kind.as_string()).map(|kind| kind.to_string()).collect(), strings
+        );
+        targets.push(CrateTarget {
+            kind: kind,
+            name: try!(field(manifest_path, target, "name", Json::as_string)).to_string(),
+            src_path: PathBuf::from(try!(field(manifest_path, target, "src_path", Json::as_string))),
+            crate_types: crate_types
+        });
+    }
+    targets.sort_by(|a, b| (a.kind.order(), &a.src_path).cmp(&(b.kind.order(), &b.src_path)));
+
+    let mut features = BTreeMap::new();
+    if let Some(declared) = package.find("features").and_then(|features| features.as_object()) {
+        for (feature, implied) in declared.iter() {
+            features.insert(feature.clone(), strings(implied));
+        }
+    }
+    // an optional dependency is a feature of the same name unless some feature refers to it with `dep:`
+    let explicit = features.values()
+        .flat_map(|implied| implied.iter())
+        .filter(|feature| feature.starts_with("dep:"))
+        .map(|feature| feature[4..].to_string())
+        .collect::<BTreeSet<_>>();
+    for dependency in package.find("dependencies").and_then(|x| x.as_array()).map_or(&[][..], |x| &x[..]) {
+        if dependency.find("optional").and_then(|x| x.as_boolean()) != Some(true) {
+            continue;
+        }
+        let name = dependency.find("rename").and_then(|x| x.as_string())
+            .or_else(|| dependency.find("name").and_then(|x| x.as_string()));
+        if let Some(name) = name {
+            if !explicit.contains(name) && !features.contains_key(name) {
+                features.insert(name.to_string(), Vec::new());
+            }
+        }
+    }
+
+    Ok(Package { name: name, root: root, targets: targets, features: features })
+}
+
+
+/// Value of the `key` field converted by `convert`, e.g. `Json::as_string`.
+fn field<'a, T, F>(manifest_path: &Path, json: &'a Json, key: &str, convert: F) -> ManifestResult<T>
+    where F: Fn(&'a Json) -> Option<T>
+{
+    json.find(key).and_then(convert).ok_or_else(
+        || ManifestError::new(manifest_path, format!("unexpected metadata: no valid `{}` field", key))
+    )
+}
+
+
+/// Strings of the array, other values are skipped.
+fn strings(json: &Json) -> Vec<String> {
+    json.as_array().map_or(Vec::new(), |values| {
+        values.iter().filter_map(|value| value.as_string()).map(|value| value.to_string()).collect()
+    })
+}
+
+
+pub type ManifestResult<T> = Result<T, ManifestError>;
+
+
+#[derive(Debug)]
+pub struct ManifestError {
+    pub path: PathBuf,
+    pub message: String
+}
+
+
+impl ManifestError {
+    fn new(path: &Path, message: String) -> ManifestError {
+        ManifestError { path: path.to_path_buf(), message: message }
+    }
+}
+
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.message)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use serialize::json::Json;
+    use fixture::{temp_dir, write};
+    use super::{CrateTarget, Package, TargetKind, discover, load_libraries, load_packages};
+
+    const METADATA: &'static str = r#"{
+        "packages": [
+            {
+                "name": "member",
+                "manifest_path": "/ws/member/Cargo.toml",
+                "targets": [{"kind": ["lib"], "name": "member", "src_path": "/ws/member/src/lib.rs"}],
+                "features": {}
+            },
+            {
+                "name": "explorer",
+                "manifest_path": "/ws/Cargo.toml",
+                "targets": [
+                    {"kind": ["custom-build"], "name": "build-script-build", "src_path": "/ws/build.rs"},
+                    {"kind": ["bin"], "name": "tool", "src_path": "/ws/src/bin/tool/main.rs"},
+                    {
+                        "kind": ["proc-macro"], "crate_types": ["proc-macro"], "name": "explorer",
+                        "src_path": "/ws/src/explorer.rs"
+                    },
+                    {"kind": ["bin"], "name": "explorer", "src_path": "/ws/src/main.rs"}
+                ],
+                "features": {"default": ["std"], "std": [], "json": ["dep:serde_json"]},
+                "dependencies": [
+                    {"name": "getopts", "optional": false, "rename": null},
+                    {"name": "serde", "optional": true, "rename": null},
+                    {"name": "serde_json", "optional": true, "rename": null},
+                    {"name": "log", "optional": true, "rename": "logging"}
+                ]
+            }
+        ]
+    }"#;
+
+    fn target(kind: TargetKind, name: &str, src_path: &str, crate_type: &str) -> CrateTarget {
+        CrateTarget {
+            kind: kind, name: name.to_string(), src_path: PathBuf::from(src_path), crate_types: vec![crate_type.to_string()]
+        }
+    }
+
+    #[test]
+    fn test_load_packages() {
+        let metadata = Json::from_str(METADATA).unwrap();
+        let packages = load_packages(Path::new("/ws"), Path::new("/ws/Cargo.toml"), &metadata).unwrap();
+        assert_eq!(packages.iter().map(|x| &x.name[..]).collect::<Vec<_>>(), vec!["explorer", "member"]);
+
+        let explorer = &packages[0];
+        assert_eq!(explorer.root, PathBuf::from("/ws"));
+        assert_eq!(explorer.targets, vec![
+            target(TargetKind::Lib, "explorer", "/ws/src/explorer.rs", "proc-macro"),
+            target(TargetKind::Bin, "tool", "/ws/src/bin/tool/main.rs", "bin"),
+            target(TargetKind::Bin, "explorer", "/ws/src/main.rs", "bin")
+        ]);
+        assert_eq!(explorer.features.get("default"), Some(&vec!["std".to_string()]));
+        assert_eq!(
+            explorer.features.keys().map(|x| &x[..]).collect::<Vec<_>>(),
+            vec!["default", "json", "logging", "serde", "std"]
+        );
+
+        let member = load_packages(Path::new("/ws/member"), Path::new("/ws/member/Cargo.toml"), &metadata).unwrap();
+        assert_eq!(member.iter().map(|x| &x.name[..]).collect::<Vec<_>>(), vec!["member"]);
+
+        let invalid = Json::from_str(r#"{"packages": [{"name": "x"}]}"#).unwrap();
+        assert!(load_packages(Path::new("/ws"), Path::new("/ws/Cargo.toml"), &invalid).is_err());
+    }
+
+    // runs cargo, the shape of its output is checked by `test_load_packages`
+    #[test]
+    #[ignore]
+    fn test_discover() {
+        let root = temp_dir("discover");
+        write(&root.join("Cargo.toml"), r#"
+lib.path = "src/hello.rs"
+lib.crate-type = ["staticlib"]
+
+[package]
+name = "hello-world"
+version = "0.1.0"
+
+[dependencies]
+member = { path = "member" }
+
+[workspace]
+members = ["member"]
+"#);
+        write(&root.join("src/hello.rs"), "");
+        write(&root.join("src/bin/tool/main.rs"), "fn main() {}");
+        write(&root.join("member/Cargo.toml"), "[package]\nname = \"member\"\nversion = \"0.1.0\"\n");
+        write(&root.join("member/src/lib.rs"), "");
+
+        let root = fs::canonicalize(&root).unwrap();
+        let packages = discover(&root).unwrap();
+        assert_eq!(packages.iter().map(|x| &x.name[..]).collect::<Vec<_>>(), vec!["hello-world", "member"]);
+        assert_eq!(packages[0].targets, vec![
+            target(TargetKind::Lib, "hello_world", root.join("src/hello.rs").to_str().unwrap(), "staticlib"),
+            target(TargetKind::Bin, "tool", root.join("src/bin/tool/main.rs").to_str().unwrap(), "bin")
+        ]);
+        assert_eq!(packages[1].root, root.join("member"));
+
+        assert!(discover(&root.join("missing")).is_err());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_load_libraries() {
+        let library = |name: &str, root: &str| format!(
+            r#"{{"name": "{0}", "manifest_path": "{1}/Cargo.toml", "features": {{}},
+                "targets": [{{"kind": ["lib"], "name": "{0}", "src_path": "{1}/src/lib.rs"}}]}}"#,
+            name, root
+        );
+        let metadata = Json::from_str(&format!(r#"{{"packages": [{}, {}, {}, {}]}}"#,
+            library("getopts", "/registry/getopts-0.2.14"),
+            library("log", "/registry/log-0.3.1"),
+            library("log", "/registry/log-0.4.0"),
+            library("explorer", "/ws")
+        )).unwrap();
+
+        let libraries = load_libraries(Path::new("/ws/Cargo.toml"), &metadata).unwrap();
+        assert_eq!(libraries.into_iter().collect::<Vec<_>>(), vec![
+            ("explorer".to_string(), PathBuf::from("/ws")),
+            ("getopts".to_string(), PathBuf::from("/registry/getopts-0.2.14"))
+        ]);
+    }
+
+    #[test]
+    fn test_resolve_features() {
+        let mut features = BTreeMap::new();
+        features.insert("default".to_string(), vec!["std".to_string()]);
+        features.insert("std".to_string(), vec!["alloc".to_string(), "serde/std".to_string()]);
+        features.insert("alloc".to_string(), vec![]);
+        features.insert("nightly".to_string(), vec![]);
+        features.insert("json".to_string(), vec!["dep:serde_json".to_string()]);
+        features.insert("serde".to_string(), vec![]); // optional dependency
+        let package = Package { name: "p".into(), root: PathBuf::new(), targets: vec![], features: features };
+
+        let strings = |names: &[&str]| names.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+        assert_eq!(package.resolve_features(&[], false, false), strings(&["alloc", "default", "std"]));
+        assert_eq!(package.resolve_features(&strings(&["nightly"]), false, true), strings(&["nightly"]));
+        assert_eq!(
+            package.resolve_features(&[], true, true),
+            strings(&["alloc", "default", "json", "nightly", "serde", "std"])
+        );
+    }
+}
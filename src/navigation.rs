@@ -1,70 +1,137 @@
-use compiler_api::CrateAnalysis;
-pub use syntax::ast::NodeId;
+use compiler_api::{CrateAnalysis, DefMap, Map};
+use cfg::StrippedSpan;
+use syntax::ast::LOCAL_CRATE;
+use syntax::codemap::CodeMap;
 use std::collections::HashMap;
 
+pub use self::conversions::span_to_region;
 
-pub fn collect_mappings(crate_analysis: &CrateAnalysis) -> (Vec<ActiveRegion>, Vec<Definition>) {
+
+pub struct Mappings {
+    pub active_regions: Vec<ActiveRegion>,
+    pub definitions: Vec<Definition>,
+    pub type_hints: Vec<TypeHint>
+}
+
+
+pub fn collect_mappings(crate_analysis: &CrateAnalysis) -> Mappings {
     let ty_cx = &crate_analysis.ty_cx;
     let codemap = ty_cx.sess.codemap();
     let def_map = ty_cx.def_map.borrow();
+    let items = items::collect(ty_cx.map.krate());
+    let members = members::collect(ty_cx);
 
     let def_map_mappings = def_map.iter()
         .map(|(&node_id, path)| (
             conversions::node_id_to_span(&ty_cx.map, node_id)
                 .and_then(|span| conversions::span_to_region(codemap, span)),
-            conversions::path_resolution_to_span(&ty_cx.map, path)
-                .and_then(|span| conversions::span_to_region(codemap, span))
+            conversions::path_resolution_to_target(ty_cx, path, &items.field_spans)
+        ));
+
+    let member_mappings = members.iter()
+        .map(|member| (
+            conversions::span_to_region(codemap, member.span),
+            conversions::member_to_target(ty_cx, member, &items.field_spans)
         ));
 
-    let mappings = def_map_mappings
+    let (active_regions, definitions) = link(&ty_cx.map, codemap, &items, def_map_mappings.chain(member_mappings));
+
+    let type_hints = type_hints::collect(ty_cx).into_iter()
+        .filter_map(|(span, ty)| conversions::span_to_region(codemap, span).map(
+            |region| TypeHint { region: region, ty: ty }
+        ))
+        .collect();
+
+    Mappings { active_regions: active_regions, definitions: definitions, type_hints: type_hints }
+}
+
+
+/// Navigation known before the type checking: the items and the paths referring to local definitions.
+/// It is all there is if the type checking fails.
+pub fn collect_resolved<'ast>(map: &Map<'ast>, codemap: &CodeMap, def_map: &DefMap) -> Mappings {
+    let items = items::collect(map.krate());
+    let mappings = def_map.borrow().iter()
+        .map(|(&node_id, path)| (
+            conversions::node_id_to_span(map, node_id)
+                .and_then(|span| conversions::span_to_region(codemap, span)),
+            conversions::path_resolution_to_def_id(path)
+                .and_then(|def_id| if def_id.krate == LOCAL_CRATE { Some(def_id.node) } else { None })
+                .and_then(|node_id| conversions::local_target(map, codemap, node_id, &items.field_spans))
+        ))
+        .collect::<Vec<_>>();
+
+    let (active_regions, definitions) = link(map, codemap, &items, mappings.into_iter());
+    Mappings { active_regions: active_regions, definitions: definitions, type_hints: Vec::new() }
+}
+
+
+/// Definitions of all the items and of the referred bindings, and the regions referring to them
+/// or to external items.
+fn link<'ast, I>(map: &Map<'ast>, codemap: &CodeMap, items: &items::Items, mappings: I) -> (Vec<ActiveRegion>, Vec<Definition>)
+    where I: Iterator<Item=(Option<Region>, Option<conversions::Target>)>
+{
+    // items are registered before the references, so the ones nothing refers to are known too
+    let mut registry = DefinitionRegistry::new();
+    for &node_id in items.ids.iter() {
+        if let Some(conversions::Target::Local(region, item_path, kind)) =
+                conversions::local_target(map, codemap, node_id, &items.field_spans) {
+            registry.register(region, item_path, kind);
+        }
+    }
+
+    let mappings = mappings
         .filter_map(has_both)
-        .filter(|&(ref a, ref b)| a != b);
+        .filter(|&(ref a, ref b)| match *b {
+            conversions::Target::Local(ref def_region, _, _) => a != def_region,
+            conversions::Target::External(_) => true
+        });
 
-    let mut definitions_generator = UniqRegionRegistry::new(
-        |region, id| Definition { region: region, id: id}
-    );
     let mut active_regions = Vec::new();
-    for (active_region, def_region) in mappings {
-        let def_id = definitions_generator.get_or_register(def_region.clone());
+    for (active_region, target) in mappings {
+        let target = match target {
+            conversions::Target::Local(def_region, item_path, kind) => {
+                let filename = def_region.filename.clone();
+                Target::Definition(filename, registry.register(def_region, item_path, kind))
+            },
+            conversions::Target::External(item) => Target::External(item)
+        };
         active_regions.push(
-            ActiveRegion { def: (def_region.filename, def_id), region: active_region}
+            ActiveRegion { target: target, region: active_region }
         );
     }
-    (active_regions, definitions_generator.generate())
+    (active_regions, registry.definitions)
 }
 
 
-struct UniqRegionRegistry<'a, T> {
-    region_to_id: HashMap<Region, u32>,
-    id: u32,
-    constructor: Box<Fn(Region, u32) -> T + 'a>
+pub fn collect_inactive_regions(codemap: &CodeMap, stripped: Vec<StrippedSpan>) -> Vec<InactiveRegion> {
+    stripped.into_iter()
+        .filter_map(|x| conversions::span_to_region(codemap, x.span).map(
+            |region| InactiveRegion { region: region, predicate: x.predicate }
+        ))
+        .collect()
 }
 
 
-impl<'a, T> UniqRegionRegistry<'a, T> {
-    pub fn new<F: Fn(Region, u32) -> T + 'a>(constructor: F) -> UniqRegionRegistry<'a, T> {
-        UniqRegionRegistry {
-            region_to_id: HashMap::new(),
-            id: 0,
-            constructor: Box::new(constructor)
-        }
-    }
+/// Definitions by their regions, ids are given in the order of registration starting from 1.
+struct DefinitionRegistry {
+    ids: HashMap<Region, u32>,
+    definitions: Vec<Definition>
+}
 
-    pub fn get_or_register(&mut self, region: Region) -> u32 {
-        if let Some(&id) = self.region_to_id.get(&region) {
-            return id;
-        }
-        self.id += 1;
-        self.region_to_id.insert(region, self.id);
-        self.id
+
+impl DefinitionRegistry {
+    fn new() -> DefinitionRegistry {
+        DefinitionRegistry { ids: HashMap::new(), definitions: Vec::new() }
     }
 
-    pub fn generate(self) -> Vec<T> {
-        let mut items = Vec::new();
-        for (region, id) in self.region_to_id {
-            items.push((self.constructor)(region, id));
+    fn register(&mut self, region: Region, path: Option<String>, kind: &'static str) -> u32 {
+        if let Some(&id) = self.ids.get(&region) {
+            return id;
         }
-        items
+        let id = self.definitions.len() as u32 + 1;
+        self.ids.insert(region.clone(), id);
+        self.definitions.push(Definition { id: id, region: region, path: path, kind: kind });
+        id
     }
 }
 
@@ -80,42 +147,116 @@ fn has_both<T>(tuple: (Option<T>, Option<T>)) -> Option<(T, T)> {
 #[derive(Clone)]
 pub struct Definition {
     pub id: u32,
-    pub region: Region
+    pub region: Region, // the name of the definition, or all of it when the name isn't known
+    pub path: Option<String>, // item path relative to the crate root, e.g. `module::Item`
+    pub kind: &'static str // `fn`, `struct`, `field`, `method`, `local`, ... see `conversions::local_kind`
+}
+
+
+impl Definition {
+    /// Last segment of the item path, or the source of the name for bindings and fields.
+    pub fn name<'a>(&'a self, src: &'a str) -> &'a str {
+        match self.path {
+            Some(ref path) => path.rsplit("::").next().unwrap_or(&path[..]),
+            None if self.region.start <= self.region.end && self.region.end < src.len() =>
+                &src[self.region.start .. self.region.end + 1],
+            None => ""
+        }
+    }
 }
 
 
 pub struct ActiveRegion {
-    pub def: (String, u32),
+    pub target: Target,
     pub region: Region
 }
 
 
+/// Inferred type of a binding or an expression.
+pub struct TypeHint {
+    pub region: Region,
+    pub ty: String
+}
+
+
+/// Code which was not compiled because of the failed cfg predicate.
+pub struct InactiveRegion {
+    pub region: Region,
+    pub predicate: String
+}
+
+
+pub enum Target {
+    Definition(String, u32), // filename and definition id
+    External(ExternalItem)
+}
+
+
+/// Item defined in another crate, described by its path from the crate metadata.
+#[derive(Clone, Debug)]
+pub struct ExternalItem {
+    pub krate: String,
+    pub path: Vec<String>, // path inside the crate, without crate name
+    pub kind: &'static str, // the same kinds as of definitions
+    /// Enum of a variant, struct of a field, or struct, enum or trait of a method:
+    /// rustdoc documents them on its page.
+    pub parent: Option<Box<ExternalItem>>
+}
+
+
+impl ExternalItem {
+    pub fn name(&self) -> &str {
+        self.path.last().map_or("", |name| &name[..])
+    }
+
+    pub fn full_path(&self) -> String {
+        self.path.connect("::")
+    }
+}
+
+
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
 pub struct Region {
     pub filename: String,
-    pub start: usize, //inclusive character position
-    pub end: usize, //inclusive character position
+    pub start: usize, // inclusive byte offset from the start of the file
+    pub end: usize, // inclusive byte offset from the start of the file
 }
 
 
 mod conversions {
-    use super::Region;
+    use super::{Region, ExternalItem};
+    use super::members::Member;
 
     use rustc::middle::def::{Def, PathResolution};
+    use rustc::middle::ty;
     use syntax::ast::{DefId, NodeId};
-    use syntax::ast_map::Map;
+    use syntax::ast_map::{Map, Node};
     use syntax::codemap::{Span, CodeMap};
+    use std::collections::HashMap;
+
+
+    pub enum Target {
+        Local(Region, Option<String>, &'static str), // region, item path and kind of the definition
+        External(ExternalItem)
+    }
 
 
+    /// Region uses the same coordinates as `lexer::Interval`: byte offsets within the file.
+    /// Spans crossing files, e.g. the ones made by macros from tokens of several files, have no region.
     pub fn span_to_region(codemap: &CodeMap, span: Span) -> Option<Region> {
         use syntax::codemap::{Pos, DUMMY_SP};
-        if span == DUMMY_SP {
+        if span == DUMMY_SP || span.hi <= span.lo {
+            return None;
+        }
+        let lo = codemap.lookup_byte_offset(span.lo);
+        let hi = codemap.lookup_byte_offset(span.hi);
+        if lo.fm.start_pos != hi.fm.start_pos {
             return None;
         }
         Some(Region {
-            start: codemap.bytepos_to_file_charpos(span.lo).to_usize(),
-            end: codemap.bytepos_to_file_charpos(span.hi).to_usize() - 1,
-            filename: codemap.span_to_filename(span)
+            start: lo.pos.to_usize(),
+            end: hi.pos.to_usize() - 1,
+            filename: lo.fm.name.clone()
         })
     }
 
@@ -125,17 +266,137 @@ mod conversions {
     }
 
 
-    pub fn path_resolution_to_span<'ast>(map: &Map<'ast>, path: &PathResolution) -> Option<Span> {
-        path_resolution_to_node_id(path).and_then(|node_id| node_id_to_span(map, node_id))
+    pub fn path_resolution_to_target(
+        ty_cx: &ty::ctxt, path: &PathResolution, known_spans: &HashMap<NodeId, Span>
+    ) -> Option<Target> {
+        let def = path.full_def();
+        let target = path_resolution_to_def_id(path).and_then(
+            |def_id| def_id_to_target(ty_cx, def_id, def_kind(def), known_spans)
+        );
+        match (target, def) {
+            (Some(Target::External(mut item)), Def::DefVariant(enum_id, _, _)) => {
+                item.parent = Some(Box::new(external_item(ty_cx, enum_id, "enum")));
+                Some(Target::External(item))
+            },
+            (target, _) => target
+        }
+    }
+
+
+    /// Fields of other crates are documented on the page of their struct, so it's their parent.
+    pub fn member_to_target(
+        ty_cx: &ty::ctxt, member: &Member, known_spans: &HashMap<NodeId, Span>
+    ) -> Option<Target> {
+        match (def_id_to_target(ty_cx, member.def_id, member.kind, known_spans), member.struct_id) {
+            (Some(Target::External(mut item)), Some(struct_id)) => {
+                item.parent = Some(Box::new(external_item(ty_cx, struct_id, "struct")));
+                Some(Target::External(item))
+            },
+            (target, _) => target
+        }
+    }
+
+
+    /// `known_spans` covers definitions which are not present in the ast map (e.g. struct fields).
+    pub fn def_id_to_target(
+        ty_cx: &ty::ctxt, def_id: DefId, kind: &'static str, known_spans: &HashMap<NodeId, Span>
+    ) -> Option<Target> {
+        use syntax::ast::LOCAL_CRATE;
+
+        if def_id.krate == LOCAL_CRATE {
+            local_target(&ty_cx.map, ty_cx.sess.codemap(), def_id.node, known_spans)
+        } else {
+            Some(Target::External(external_item(ty_cx, def_id, kind)))
+        }
     }
 
 
-    fn path_resolution_to_node_id(path: &PathResolution) -> Option<NodeId> {
-        path_resolution_to_def_id(path).and_then(|def_id| def_id_to_node_id(def_id))
+    /// Definition of the crate, it is found without the type context.
+    pub fn local_target<'ast>(
+        map: &Map<'ast>, codemap: &CodeMap, node_id: NodeId, known_spans: &HashMap<NodeId, Span>
+    ) -> Option<Target> {
+        let field_span = known_spans.get(&node_id).cloned();
+        let span = match field_span.or_else(|| node_id_to_span(map, node_id)) {
+            Some(span) => span,
+            None => return None
+        };
+        let (kind, name) = match field_span {
+            Some(_) => ("field", codemap.span_to_snippet(span).ok().and_then(|snippet| field_name(&snippet))),
+            None => (local_kind(map, node_id), local_name(map, node_id))
+        };
+        name_region(codemap, span, name)
+            .map(|region| Target::Local(region, local_item_path(map, node_id), kind))
     }
 
 
-    fn path_resolution_to_def_id(path: &PathResolution) -> Option<DefId> {
+    /// Region of the name inside of the definition, e.g. of `foo` in `pub fn foo() {}`,
+    /// or of the whole definition if the name isn't known or found.
+    fn name_region(codemap: &CodeMap, span: Span, name: Option<String>) -> Option<Region> {
+        let region = match span_to_region(codemap, span) {
+            Some(region) => region,
+            None => return None
+        };
+        let offset = name.as_ref().and_then(|name| {
+            codemap.span_to_snippet(span).ok().and_then(|snippet| find_word(&snippet, name))
+        });
+        match (offset, name) {
+            (Some(offset), Some(name)) => Some(Region {
+                start: region.start + offset,
+                end: region.start + offset + name.len() - 1,
+                filename: region.filename
+            }),
+            _ => Some(region)
+        }
+    }
+
+
+    /// Byte offset of the first occurrence of `word` which isn't a part of a longer identifier.
+    pub fn find_word(text: &str, word: &str) -> Option<usize> {
+        let is_ident_char = |ch: char| ch.is_alphanumeric() || ch == '_';
+        if word.is_empty() {
+            return None;
+        }
+        let mut from = 0;
+        while let Some(index) = text[from..].find(word).map(|index| from + index) {
+            let end = index + word.len();
+            let before = text[..index].chars().next_back();
+            let after = text[end..].chars().next();
+            if !before.map_or(false, &is_ident_char) && !after.map_or(false, &is_ident_char) {
+                return Some(index);
+            }
+            from = end;
+        }
+        None
+    }
+
+
+    /// `pub name: Type` -> `name`, tuple fields have no name.
+    pub fn field_name(field: &str) -> Option<String> {
+        field.find(':')
+            .and_then(|colon| field[..colon].trim().rsplit(' ').next())
+            .map(|name| name.to_string())
+    }
+
+
+    fn local_name<'ast>(map: &Map<'ast>, node_id: NodeId) -> Option<String> {
+        use syntax::ast;
+
+        match map.find(node_id) {
+            Some(Node::NodeItem(item)) => Some(item.ident.to_string()),
+            Some(Node::NodeForeignItem(item)) => Some(item.ident.to_string()),
+            Some(Node::NodeTraitItem(item)) => Some(item.ident.to_string()),
+            Some(Node::NodeImplItem(item)) => Some(item.ident.to_string()),
+            Some(Node::NodeVariant(variant)) => Some(variant.node.name.to_string()),
+            Some(Node::NodeLocal(pat)) | Some(Node::NodeArg(pat)) => match pat.node {
+                ast::PatIdent(_, ref ident, _) => Some(ident.node.to_string()),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+
+    pub fn path_resolution_to_def_id(path: &PathResolution) -> Option<DefId> {
         match path.full_def() {
             Def::DefPrimTy(_) | Def::DefSelfTy(..) => None,
             _ => Some(path.def_id())
@@ -143,13 +404,389 @@ mod conversions {
     }
 
 
-    fn def_id_to_node_id(def_id: DefId) -> Option<NodeId> {
-        use syntax::ast::LOCAL_CRATE;
+    fn local_item_path<'ast>(map: &Map<'ast>, node_id: NodeId) -> Option<String> {
+        match map.find(node_id) {
+            Some(Node::NodeItem(..)) |
+            Some(Node::NodeForeignItem(..)) |
+            Some(Node::NodeTraitItem(..)) |
+            Some(Node::NodeImplItem(..)) |
+            Some(Node::NodeVariant(..)) => {
+                Some(map.with_path(node_id, |path| path_to_strings(path).connect("::")))
+            },
+            _ => None
+        }
+    }
 
-        if def_id.krate == LOCAL_CRATE {
-            Some(def_id.node)
+
+    /// Kind of the local definition: `fn`, `struct`, `enum`, `variant`, `trait`, `type`, `const`,
+    /// `static`, `mod`, `method` or `local` for bindings.
+    fn local_kind<'ast>(map: &Map<'ast>, node_id: NodeId) -> &'static str {
+        use syntax::ast;
+
+        match map.find(node_id) {
+            Some(Node::NodeItem(item)) => match item.node {
+                ast::ItemFn(..) => "fn",
+                ast::ItemStruct(..) => "struct",
+                ast::ItemEnum(..) => "enum",
+                ast::ItemTrait(..) => "trait",
+                ast::ItemTy(..) => "type",
+                ast::ItemConst(..) => "const",
+                ast::ItemStatic(..) => "static",
+                ast::ItemMod(..) | ast::ItemForeignMod(..) => "mod",
+                ast::ItemExternCrate(..) => "crate",
+                _ => "item"
+            },
+            Some(Node::NodeForeignItem(item)) => match item.node {
+                ast::ForeignItemFn(..) => "fn",
+                ast::ForeignItemStatic(..) => "static"
+            },
+            Some(Node::NodeTraitItem(item)) => match item.node {
+                ast::ConstTraitItem(..) => "const",
+                ast::MethodTraitItem(..) => "method",
+                ast::TypeTraitItem(..) => "type"
+            },
+            Some(Node::NodeImplItem(item)) => match item.node {
+                ast::ConstImplItem(..) => "const",
+                ast::TypeImplItem(..) => "type",
+                _ => "method"
+            },
+            Some(Node::NodeVariant(..)) => "variant",
+            Some(Node::NodeStructCtor(..)) => "struct",
+            Some(Node::NodeLocal(..)) | Some(Node::NodeArg(..)) => "local",
+            _ => "item"
+        }
+    }
+
+
+    fn external_item(ty_cx: &ty::ctxt, def_id: DefId, kind: &'static str) -> ExternalItem {
+        // for external items path starts with the crate name
+        let mut path = ty::with_path(ty_cx, def_id, path_to_strings);
+        let krate = if path.is_empty() { String::new() } else { path.remove(0) };
+        let parent = if kind == "method" {
+            method_parent(ty_cx, def_id).map(|(parent_id, parent_kind)| {
+                Box::new(external_item(ty_cx, parent_id, parent_kind))
+            })
         } else {
             None
+        };
+        ExternalItem { krate: krate, path: path, kind: kind, parent: parent }
+    }
+
+
+    /// Trait of the method, or the struct or enum it's implemented for.
+    fn method_parent(ty_cx: &ty::ctxt, def_id: DefId) -> Option<(DefId, &'static str)> {
+        match ty::impl_or_trait_item(ty_cx, def_id).container() {
+            ty::TraitContainer(trait_id) => Some((trait_id, "trait")),
+            ty::ImplContainer(impl_id) => match ty::lookup_item_type(ty_cx, impl_id).ty.sty {
+                ty::TyStruct(struct_id, _) => Some((struct_id, "struct")),
+                ty::TyEnum(enum_id, _) => Some((enum_id, "enum")),
+                _ => None
+            }
+        }
+    }
+
+
+    fn path_to_strings<I: Iterator>(path: I) -> Vec<String> where I::Item: ToString {
+        path.map(|elem| elem.to_string()).collect()
+    }
+
+
+    /// Kind of item in terms of rustdoc file names and anchors (`struct.Vec.html`, `#variant.Some`, ...).
+    fn def_kind(def: Def) -> &'static str {
+        match def {
+            Def::DefFn(..) => "fn",
+            Def::DefMethod(..) => "method",
+            Def::DefStruct(..) => "struct",
+            Def::DefTy(_, true) => "enum",
+            Def::DefVariant(..) => "variant",
+            Def::DefTy(_, false) => "type",
+            Def::DefTrait(..) => "trait",
+            Def::DefConst(..) => "const",
+            Def::DefStatic(..) => "static",
+            Def::DefMod(..) => "mod",
+            _ => "item"
         }
     }
 }
+
+
+/// Everything which can be navigated to, whether the crate refers to it or not:
+/// items, trait and impl items, variants and named fields.
+mod items {
+    use syntax::ast::{self, NodeId};
+    use syntax::codemap::Span;
+    use syntax::visit::{self, Visitor};
+    use std::collections::HashMap;
+
+
+    pub struct Items {
+        pub ids: Vec<NodeId>,
+        /// Struct fields are not present in the ast map, so their spans are kept here.
+        pub field_spans: HashMap<NodeId, Span>
+    }
+
+
+    pub fn collect(krate: &ast::Crate) -> Items {
+        let mut collector = ItemsCollector { items: Items { ids: Vec::new(), field_spans: HashMap::new() } };
+        visit::walk_crate(&mut collector, krate);
+        collector.items
+    }
+
+
+    struct ItemsCollector {
+        items: Items
+    }
+
+
+    impl<'v> Visitor<'v> for ItemsCollector {
+        fn visit_item(&mut self, item: &'v ast::Item) {
+            match item.node {
+                // nameless or not defining anything
+                ast::ItemImpl(..) | ast::ItemDefaultImpl(..) | ast::ItemUse(..) | ast::ItemForeignMod(..) => (),
+                _ => self.items.ids.push(item.id)
+            }
+            visit::walk_item(self, item);
+        }
+
+        fn visit_foreign_item(&mut self, item: &'v ast::ForeignItem) {
+            self.items.ids.push(item.id);
+            visit::walk_foreign_item(self, item);
+        }
+
+        fn visit_trait_item(&mut self, item: &'v ast::TraitItem) {
+            self.items.ids.push(item.id);
+            visit::walk_trait_item(self, item);
+        }
+
+        fn visit_impl_item(&mut self, item: &'v ast::ImplItem) {
+            self.items.ids.push(item.id);
+            visit::walk_impl_item(self, item);
+        }
+
+        fn visit_variant(&mut self, variant: &'v ast::Variant, generics: &'v ast::Generics) {
+            self.items.ids.push(variant.node.id);
+            visit::walk_variant(self, variant, generics);
+        }
+
+        fn visit_struct_field(&mut self, field: &'v ast::StructField) {
+            if let ast::NamedField(..) = field.node.kind {
+                self.items.ids.push(field.node.id);
+            }
+            self.items.field_spans.insert(field.node.id, field.span);
+            visit::walk_struct_field(self, field);
+        }
+    }
+}
+
+
+/// Resolution of method calls and field accesses, which are not covered by the def map.
+mod members {
+    use rustc::middle::ty::{self, MethodCall};
+    use syntax::ast::{self, DefId, Expr};
+    use syntax::codemap::Span;
+    use syntax::visit::{self, Visitor};
+
+
+    /// Method name or field name in an expression.
+    pub struct Member {
+        pub span: Span,
+        pub def_id: DefId,
+        pub kind: &'static str, // `method` or `field`
+        /// Struct of the field.
+        pub struct_id: Option<DefId>
+    }
+
+
+    pub fn collect(ty_cx: &ty::ctxt) -> Vec<Member> {
+        let mut collector = MembersCollector { ty_cx: ty_cx, references: Vec::new() };
+        visit::walk_crate(&mut collector, ty_cx.map.krate());
+        collector.references
+    }
+
+
+    struct MembersCollector<'a, 'tcx: 'a> {
+        ty_cx: &'a ty::ctxt<'tcx>,
+        references: Vec<Member>
+    }
+
+
+    impl<'a, 'tcx> MembersCollector<'a, 'tcx> {
+        fn method_def_id(&self, expr: &Expr) -> Option<DefId> {
+            let method_map = self.ty_cx.method_map.borrow();
+            method_map.get(&MethodCall::expr(expr.id)).map(|callee| match callee.origin {
+                ty::MethodStatic(def_id) | ty::MethodStaticClosure(def_id) => def_id,
+                ty::MethodTypeParam(ty::MethodParam { ref trait_ref, method_num, .. }) |
+                ty::MethodTraitObject(ty::MethodObject { ref trait_ref, method_num, .. }) => {
+                    ty::trait_item(self.ty_cx, trait_ref.def_id, method_num).def_id()
+                }
+            })
+        }
+
+        fn push_method(&mut self, span: Span, expr: &Expr) {
+            if let Some(def_id) = self.method_def_id(expr) {
+                self.references.push(Member { span: span, def_id: def_id, kind: "method", struct_id: None });
+            }
+        }
+
+        fn push_field(&mut self, span: Span, struct_ty: ty::Ty<'tcx>, name: ast::Name) {
+            if let ty::TyStruct(struct_id, _) = struct_ty.sty {
+                let field = ty::lookup_struct_fields(self.ty_cx, struct_id).into_iter()
+                    .find(|field| field.name == name);
+                if let Some(field) = field {
+                    self.references.push(Member {
+                        span: span, def_id: field.id, kind: "field", struct_id: Some(struct_id)
+                    });
+                }
+            }
+        }
+    }
+
+
+    impl<'a, 'tcx, 'v> Visitor<'v> for MembersCollector<'a, 'tcx> {
+        fn visit_expr(&mut self, expr: &'v Expr) {
+            match expr.node {
+                ast::ExprMethodCall(ref ident, _, _) => self.push_method(ident.span, expr),
+                ast::ExprField(ref base, ref ident) => {
+                    // autoderef of the base is recorded as its adjustment
+                    let base_ty = ty::expr_ty_adjusted(self.ty_cx, base);
+                    self.push_field(ident.span, base_ty, ident.node.name);
+                },
+                ast::ExprStruct(_, ref fields, _) => {
+                    let struct_ty = ty::expr_ty(self.ty_cx, expr);
+                    for field in fields {
+                        self.push_field(field.ident.span, struct_ty, field.ident.node.name);
+                    }
+                },
+                _ => ()
+            }
+            visit::walk_expr(self, expr);
+        }
+    }
+}
+
+
+/// Inferred types of bindings, paths, field accesses and method calls.
+mod type_hints {
+    use rustc::middle::ty;
+    use syntax::ast::{self, Expr, Pat, NodeId};
+    use syntax::codemap::Span;
+    use syntax::visit::{self, Visitor};
+
+
+    pub fn collect(ty_cx: &ty::ctxt) -> Vec<(Span, String)> {
+        let mut collector = TypeHintsCollector { ty_cx: ty_cx, hints: Vec::new() };
+        visit::walk_crate(&mut collector, ty_cx.map.krate());
+        collector.hints
+    }
+
+
+    struct TypeHintsCollector<'a, 'tcx: 'a> {
+        ty_cx: &'a ty::ctxt<'tcx>,
+        hints: Vec<(Span, String)>
+    }
+
+
+    impl<'a, 'tcx> TypeHintsCollector<'a, 'tcx> {
+        fn push(&mut self, span: Span, node_id: NodeId) {
+            if let Some(ty) = ty::node_id_to_type_opt(self.ty_cx, node_id) {
+                self.hints.push((span, format!("{}", ty)));
+            }
+        }
+    }
+
+
+    impl<'a, 'tcx, 'v> Visitor<'v> for TypeHintsCollector<'a, 'tcx> {
+        fn visit_expr(&mut self, expr: &'v Expr) {
+            match expr.node {
+                ast::ExprPath(..) => self.push(expr.span, expr.id),
+                ast::ExprField(_, ref ident) |
+                ast::ExprMethodCall(ref ident, _, _) => self.push(ident.span, expr.id),
+                _ => ()
+            }
+            visit::walk_expr(self, expr);
+        }
+
+        fn visit_pat(&mut self, pat: &'v Pat) {
+            if let ast::PatIdent(_, ref ident, _) = pat.node {
+                self.push(ident.span, pat.id);
+            }
+            visit::walk_pat(self, pat);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use syntax::codemap::{CodeMap, Span, BytePos, NO_EXPANSION};
+    use super::conversions::{field_name, find_word, span_to_region};
+    use super::{Definition, Region};
+
+    #[test]
+    fn test_multibyte_span_to_region() {
+        const FIRST: &'static str = "// ascii\n";
+        const SECOND: &'static str = "/* Ünïcödé 🦀 */ fn main() {}\n";
+        let codemap = CodeMap::new();
+        codemap.new_filemap("first.rs".into(), FIRST.into());
+        let filemap = codemap.new_filemap("second.rs".into(), SECOND.into());
+
+        let start = SECOND.find("main").unwrap();
+        let span = Span {
+            lo: BytePos(filemap.start_pos.0 + start as u32),
+            hi: BytePos(filemap.start_pos.0 + (start + "main".len()) as u32),
+            expn_id: NO_EXPANSION
+        };
+
+        assert_eq!(
+            span_to_region(&codemap, span),
+            Some(Region { filename: "second.rs".into(), start: start, end: start + 3 })
+        );
+    }
+
+    #[test]
+    fn test_span_crossing_files() {
+        let codemap = CodeMap::new();
+        let first = codemap.new_filemap("first.rs".into(), "fn first() {}\n".into());
+        let second = codemap.new_filemap("second.rs".into(), "fn second() {}\n".into());
+        let span = Span {
+            lo: BytePos(first.start_pos.0 + 3),
+            hi: BytePos(second.start_pos.0 + 9),
+            expn_id: NO_EXPANSION
+        };
+        assert_eq!(span_to_region(&codemap, span), None);
+
+        let reversed = Span { lo: span.hi, hi: span.lo, expn_id: NO_EXPANSION };
+        assert_eq!(span_to_region(&codemap, reversed), None);
+    }
+
+    #[test]
+    fn test_definition_name() {
+        const SOURCE: &'static str = "struct Point(u8);\nstruct S { pub x: u8 }\nfn f() { let y = 1; }\n";
+        let definition = |start: usize, end: usize, path: Option<&str>, kind: &'static str| Definition {
+            id: 1,
+            region: Region { filename: "main.rs".into(), start: start, end: end },
+            path: path.map(|path| path.to_string()),
+            kind: kind
+        };
+
+        assert_eq!(definition(0, 16, Some("module::Point"), "struct").name(SOURCE), "Point");
+        assert_eq!(definition(33, 33, None, "field").name(SOURCE), "x");
+        assert_eq!(definition(54, 54, None, "local").name(SOURCE), "y");
+        assert_eq!(definition(54, 100, None, "local").name(SOURCE), "");
+    }
+
+    #[test]
+    fn test_find_word() {
+        assert_eq!(find_word("pub fn get_foo(foo: u8) {}", "foo"), Some(15));
+        assert_eq!(find_word("struct Foo_;", "Foo"), None);
+        assert_eq!(find_word("enum Ёж { Ёж }", "Ёж"), Some(5));
+        assert_eq!(find_word("fn f() {}", ""), None);
+    }
+
+    #[test]
+    fn test_field_name() {
+        assert_eq!(field_name("pub x: u8"), Some("x".to_string()));
+        assert_eq!(field_name("y: Vec<u8>"), Some("y".to_string()));
+        assert_eq!(field_name("pub u8"), None);
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+
+/// Writes rendered files under the output root, creating directories on the way.
+/// Files which already have the same content are left as is, so their modification time is kept.
+pub struct OutputWriter {
+    root: PathBuf,
+    written: HashSet<PathBuf>
+}
+
+
+impl OutputWriter {
+    pub fn new(root: PathBuf) -> OutputWriter {
+        OutputWriter { root: root, written: HashSet::new() }
+    }
+
+    pub fn write<T: AsRef<str>>(&mut self, relative_path: &Path, data: &T) -> OutputResult<()> {
+        let path = self.root.join(relative_path);
+        if has_content(&path, data.as_ref()) {
+            log_debug!("unchanged {:?}", path);
+            self.written.insert(path);
+            return Ok(());
+        }
+        log_debug!("write {:?}", path);
+
+        if let Some(parent) = path.parent() {
+            try!(fs::create_dir_all(parent).map_err(|err| OutputError::new(parent, err)));
+        }
+        let mut file = try!(File::create(&path).map_err(|err| OutputError::new(&path, err)));
+        try!(file.write_all(data.as_ref().as_bytes()).map_err(|err| OutputError::new(&path, err)));
+
+        self.written.insert(path);
+        Ok(())
+    }
+
+    /// Number of files written so far, including the unchanged ones.
+    pub fn written_count(&self) -> usize {
+        self.written.len()
+    }
+
+    /// Removes `.html` files which were not written by this writer, e.g. left by previous runs.
+    /// Symlinks are neither followed nor removed, so nothing outside of the output root is touched.
+    pub fn remove_stale(&self) -> OutputResult<Vec<PathBuf>> {
+        let mut stale = Vec::new();
+        try!(self.find_stale(&self.root, &mut stale));
+        for path in stale.iter() {
+            try!(fs::remove_file(path).map_err(|err| OutputError::new(path, err)));
+        }
+        Ok(stale)
+    }
+
+    fn find_stale(&self, dir: &Path, stale: &mut Vec<PathBuf>) -> OutputResult<()> {
+        let entries = try!(fs::read_dir(dir).map_err(|err| OutputError::new(dir, err)));
+        for entry in entries {
+            let path = try!(entry.map_err(|err| OutputError::new(dir, err))).path();
+            let metadata = try!(fs::symlink_metadata(&path).map_err(|err| OutputError::new(&path, err)));
+            if metadata.file_type().is_symlink() {
+                continue;
+            }
+            if metadata.is_dir() {
+                try!(self.find_stale(&path, stale));
+            } else if path.extension().map_or(false, |ext| ext == "html") && !self.written.contains(&path) {
+                stale.push(path);
+            }
+        }
+        Ok(())
+    }
+}
+
+
+fn has_content(path: &Path, data: &str) -> bool {
+    match fs::metadata(path) {
+        Ok(ref metadata) if metadata.is_file() && metadata.len() == data.len() as u64 => (),
+        _ => return false
+    }
+    let mut buf = String::new();
+    match File::open(path).and_then(|mut file| file.read_to_string(&mut buf)) {
+        Ok(_) => buf == data,
+        Err(_) => false
+    }
+}
+
+
+pub type OutputResult<T> = Result<T, OutputError>;
+
+
+#[derive(Debug)]
+pub struct OutputError {
+    pub path: PathBuf,
+    pub error: io::Error
+}
+
+
+impl OutputError {
+    fn new(path: &Path, error: io::Error) -> OutputError {
+        OutputError { path: path.to_path_buf(), error: error }
+    }
+}
+
+
+impl fmt::Display for OutputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.error)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use std::path::Path;
+    use fixture::{read, temp_dir, write};
+    use super::{OutputWriter, has_content};
+
+    #[test]
+    fn test_write_creates_directories() {
+        let root = temp_dir("write");
+        let mut writer = OutputWriter::new(root.join("out"));
+        writer.write(Path::new("src/nested/main.html"), &"<html>").unwrap();
+        assert_eq!(read(&root.join("out/src/nested/main.html")), "<html>");
+        assert_eq!(writer.written_count(), 1);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_has_content() {
+        let root = temp_dir("has_content");
+        let path = root.join("main.html");
+        write(&path, "<html>");
+        assert!(has_content(&path, "<html>"));
+        assert!(!has_content(&path, "<html></html>"));
+        assert!(!has_content(&path, "<body>"));
+        assert!(!has_content(&root.join("missing.html"), ""));
+        assert!(!has_content(&root, ""));
+
+        let mut writer = OutputWriter::new(root.clone());
+        writer.write(Path::new("main.html"), &"<html>").unwrap();
+        writer.write(Path::new("main.html"), &"<html></html>").unwrap();
+        assert_eq!(read(&path), "<html></html>");
+        assert_eq!(writer.written_count(), 1);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_remove_stale() {
+        let root = temp_dir("remove_stale");
+        let outside = root.join("outside");
+        write(&outside.join("keep.html"), "keep");
+
+        let out = root.join("out");
+        write(&out.join("src/old.html"), "old");
+        write(&out.join("src/notes.txt"), "notes");
+        symlink(&outside, out.join("linked")).unwrap();
+
+        let mut writer = OutputWriter::new(out.clone());
+        writer.write(Path::new("src/main.html"), &"main").unwrap();
+        assert_eq!(writer.remove_stale().unwrap(), vec![out.join("src/old.html")]);
+
+        assert!(!out.join("src/old.html").exists());
+        assert!(out.join("src/main.html").exists());
+        assert!(out.join("src/notes.txt").exists());
+        assert!(outside.join("keep.html").exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
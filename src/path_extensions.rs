@@ -0,0 +1,49 @@
+use std::path::{Path, PathBuf, Component};
+
+
+pub trait PathExtensions {
+    fn relative_to<P: ?Sized + AsRef<Path>>(&self, &P) -> Option<PathBuf>;
+}
+
+
+impl PathExtensions for Path {
+    fn relative_to<P: ?Sized + AsRef<Path>>(&self, base: &P) -> Option<PathBuf> {
+        let base = base.as_ref();
+
+        if self.is_absolute() != base.is_absolute() {
+            if self.is_absolute() {
+                Some(PathBuf::from(self))
+            } else {
+                None
+            }
+        } else {
+            let mut ita = self.components();
+            let mut itb = base.components();
+            let mut comps: Vec<Component> = vec![];
+            loop {
+                match (ita.next(), itb.next()) {
+                    (None, None) => break,
+                    (Some(a), None) => {
+                        comps.push(a);
+                        comps.extend(ita.by_ref());
+                        break;
+                    }
+                    (None, _) => comps.push(Component::ParentDir),
+                    (Some(a), Some(b)) if comps.is_empty() && a == b => (),
+                    (Some(a), Some(b)) if b == Component::CurDir => comps.push(a),
+                    (Some(_), Some(b)) if b == Component::ParentDir => return None,
+                    (Some(a), Some(_)) => {
+                        comps.push(Component::ParentDir);
+                        for _ in itb {
+                            comps.push(Component::ParentDir);
+                        }
+                        comps.push(a);
+                        comps.extend(ita.by_ref());
+                        break;
+                    }
+                }
+            }
+            Some(comps.iter().map(|c| c.as_os_str()).collect())
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+use navigation::{ActiveRegion, Target, Region};
+use html;
+
+
+/// Usage of a definition as it is shown on the references page.
+pub struct Reference {
+    pub filename: String,
+    pub href: String,
+    pub line: usize,
+    pub snippet: String
+}
+
+
+/// Groups regions referring to local definitions by definition id.
+pub fn collect_references(active_regions: &[ActiveRegion]) -> HashMap<u32, Vec<Region>> {
+    let mut references = HashMap::new();
+    for active_region in active_regions {
+        if let Target::Definition(_, id) = active_region.target {
+            let mut regions = references.entry(id).or_insert_with(|| Vec::new());
+            regions.push(active_region.region.clone());
+        }
+    }
+    for regions in references.values_mut() {
+        regions.sort_by(|a, b| (&a.filename, a.start).cmp(&(&b.filename, b.start)));
+    }
+    references
+}
+
+
+/// Returns 1-based line number of the byte position and the line itself.
+/// Positions past the end of the source refer to its last line.
+pub fn line_and_snippet(src: &str, position: usize) -> (usize, String) {
+    let position = if position > src.len() { src.len() } else { position };
+    let before = &src[..position];
+    let line = before.chars().filter(|&ch| ch == '\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    let line_end = src[line_start..].find('\n').map_or(src.len(), |index| line_start + index);
+    (line, src[line_start..line_end].trim().to_string())
+}
+
+
+pub fn render_references(title: &str, definition_href: &str, references: &[Reference]) -> String {
+    let mut items = String::new();
+    for reference in references {
+        items.push_str(&format!(
+            "<li><a href=\"{}\">{}:{}</a><pre><code>{}</code></pre></li>\n",
+            html::escape_attribute(&reference.href), html::escape(&reference.filename), reference.line,
+            html::escape(&reference.snippet)
+        ));
+    }
+
+    format!(concat!(
+        "<html>\n<head><title>References: {title}</title></head>\n<body>\n",
+        "<h1>References to <a href=\"{href}\"><code>{title}</code></a></h1>\n",
+        "<p>{count} found</p>\n<ul class=\"references\">\n{items}</ul>\n</body>\n</html>\n"
+    ),
+        title = html::escape(title), href = html::escape_attribute(definition_href),
+        count = references.len(), items = items
+    )
+}
+
+
+#[cfg(test)]
+mod tests {
+    use navigation::{ActiveRegion, Target, Region};
+    use super::{collect_references, line_and_snippet};
+
+    fn region(filename: &str, start: usize) -> Region {
+        Region { filename: filename.into(), start: start, end: start + 1 }
+    }
+
+    #[test]
+    fn test_collect_references() {
+        let active_regions = vec![
+            ActiveRegion { target: Target::Definition("b.rs".into(), 1), region: region("b.rs", 10) },
+            ActiveRegion { target: Target::Definition("b.rs".into(), 2), region: region("a.rs", 3) },
+            ActiveRegion { target: Target::Definition("b.rs".into(), 1), region: region("a.rs", 5) },
+        ];
+        let references = collect_references(&active_regions);
+        assert_eq!(references[&1], vec![region("a.rs", 5), region("b.rs", 10)]);
+        assert_eq!(references[&2], vec![region("a.rs", 3)]);
+    }
+
+    #[test]
+    fn test_line_and_snippet() {
+        let src = "fn main() {\n    foo();\n}\n";
+        assert_eq!(line_and_snippet(src, 0), (1, "fn main() {".to_string()));
+        assert_eq!(line_and_snippet(src, 16), (2, "foo();".to_string()));
+        assert_eq!(line_and_snippet(src, 100), (4, "".to_string()));
+    }
+
+    #[test]
+    fn test_multibyte_line_and_snippet() {
+        let src = "// привет 🦀\nfn main() {}\n";
+        let position = src.find("main").unwrap();
+        assert_eq!(line_and_snippet(src, position), (2, "fn main() {}".to_string()));
+    }
+}
@@ -1,12 +1,16 @@
+use std::collections::BTreeSet;
 use std::iter::FromIterator;
-use lexer::{Token, IntervalToSnippet};
+use lexer::Token;
 use custom_collections::{Stack, Queue};
+use highlight::{self, HighlightedToken};
+use html;
+use html::tags::Span;
 use syntax::codemap::FileMap;
 
 
 #[derive(Debug)]
 pub struct Chunk {
-    pub position: usize,
+    pub position: usize, // byte offset from the start of the file
     pub text: String
 }
 
@@ -20,66 +24,120 @@ impl Chunk {
 #[derive(Debug)]
 pub struct Wrapper {
     pub prefix: Chunk,
-    pub postfix: Chunk
+    pub postfix: Chunk,
+    /// Prefix used when the wrapper is reopened after crossing another one, the same as
+    /// `prefix` if not set. Attributes which must be unique, e.g. `id`, go to `prefix` only.
+    pub reopen: Option<String>
 }
 
 
 impl Wrapper {
     pub fn new(prefix: Chunk, postfix: Chunk) -> Wrapper {
-        Wrapper { prefix: prefix, postfix: postfix}
+        Wrapper { prefix: prefix, postfix: postfix, reopen: None }
+    }
+
+    pub fn set_reopen(mut self, reopen: String) -> Wrapper {
+        self.reopen = Some(reopen);
+        self
     }
 }
 
 
-#[allow(dead_code)]
-pub fn apply_wrappers(filemap: &FileMap, tokens: Vec<Token>, mut wrappers: Vec<Wrapper>) -> String {
+impl Wrapper {
+    /// Exclusive end of the wrapped text.
+    fn end(&self) -> usize {
+        self.postfix.position + 1
+    }
+}
+
+
+/// Renders the file with wrappers and highlighting classes of tokens applied.
+///
+/// Wrappers may start and end anywhere inside of tokens. When wrappers cross
+/// each other, the inner one is closed and reopened, so the output is always well-formed.
+pub fn apply_wrappers(filemap: &FileMap, tokens: Vec<Token>, wrappers: Vec<Wrapper>) -> String {
+    let src = filemap.src.as_ref().expect("filemap.src");
+    render_tokens(src, &highlight::highlight(&tokens), wrappers).0
+}
+
+
+/// Same as `apply_wrappers` for tokens which are already highlighted.
+/// Returns the number of wrappers which couldn't be applied as well.
+pub fn render_tokens(src: &str, tokens: &[HighlightedToken], wrappers: Vec<Wrapper>) -> (String, usize) {
+    log_debug!("total wrappers: {}", wrappers.len());
+
+    let (wrappers, not_applied): (Vec<_>, Vec<_>) = wrappers.into_iter()
+        .partition(|x| x.prefix.position <= x.postfix.position && x.end() <= src.len());
+
+    log_debug!("not applied wrappers: {}", not_applied.len());
+
+    let class_wrappers = tokens.iter()
+        .filter_map(|token| token.class.map(|class| {
+            let tag = Span::new().add_class(class);
+            Wrapper::new(
+                Chunk::new(token.start, tag.render_open()),
+                Chunk::new(token.end, tag.render_close())
+            )
+        }));
+
+    // classes go after wrappers of the same interval, so they are rendered inside
+    let mut wrappers = wrappers.into_iter().chain(class_wrappers).collect::<Vec<_>>();
     wrappers.sort_by(|a, b| {
         // left and longest go first
         (a.prefix.position, b.postfix.position)
             .cmp(&(b.prefix.position, a.postfix.position))
     });
 
-    println!("total wrappers: {}", wrappers.len());
-
-    //let mut intervals = Vec::new();
+    let mut boundaries = BTreeSet::new();
+    boundaries.insert(src.len());
+    for wrapper in wrappers.iter() {
+        boundaries.insert(wrapper.prefix.position);
+        boundaries.insert(wrapper.end());
+    }
 
     let mut wrappers = Queue::from_iter(wrappers);
-    let mut postfixes = Stack::new();
+    let mut opened = Stack::new();
 
     let mut buffer = String::new();
-    for token in tokens {
-        while wrappers.peek().map_or(false, |x| x.prefix.position == token.interval.lower_bound) {
+    let mut position = 0;
+    for boundary in boundaries {
+        buffer.push_str(&html::escape(&src[position..boundary]));
+        position = boundary;
+
+        close_wrappers(&mut opened, boundary, &mut buffer);
+
+        while wrappers.peek().map_or(false, |x| x.prefix.position == boundary) {
             let wrapper = wrappers.dequeue().expect("wrappers.dequeue()");
             buffer.push_str(&wrapper.prefix.text);
-            postfixes.push(wrapper.postfix);
+            opened.push(wrapper);
         }
+    }
+    (buffer, not_applied.len())
+}
 
-        buffer.push_str(&filemap.interval_to_snippet(&token.interval).expect("token.snippet"));
-
-        //intervals.push((token.interval.lower_bound, token.interval.upper_bound));
 
-        while postfixes.peek().map_or(false, |x| x.position == token.interval.upper_bound) {
-            let postfix = postfixes.pop().expect("postfixes.pop()");
-            buffer.push_str(&postfix.text);
+/// Closes wrappers ending at the position. Wrappers opened after them are closed
+/// too and then reopened in the original order.
+fn close_wrappers(opened: &mut Stack<Wrapper>, position: usize, buffer: &mut String) {
+    let mut reopen = Stack::new();
+    while opened.iter().any(|x| x.end() == position) {
+        let wrapper = opened.pop().expect("opened.pop()");
+        buffer.push_str(&wrapper.postfix.text);
+        if wrapper.end() != position {
+            reopen.push(wrapper);
         }
     }
-
-    println!("not applied wrappers: {}", wrappers.len());
-    println!("partialy applied wrappers: {}", postfixes.len());
-    //for wrapper in wrappers.0 {
-        //println!("wrapper. prefix: {}, postfix: {}", wrapper.prefix.position, wrapper.postfix.position);
-    //}
-    //for interval in intervals {
-        //println!("interval. {:?}", interval);
-    //}
-    buffer
+    while let Some(wrapper) = reopen.pop() {
+        buffer.push_str(wrapper.reopen.as_ref().unwrap_or(&wrapper.prefix.text));
+        opened.push(wrapper);
+    }
 }
 
 
 #[cfg(test)]
 mod tests {
     use syntax::codemap::CodeMap;
-    use super::{render, Wrapper, Chunk};
+    use super::{apply_wrappers, Wrapper, Chunk};
     use lexer::{read_tokens};
 
     const SOURCE: &'static str = "fn main() {}\n";
@@ -88,24 +146,137 @@ mod tests {
     fn test() {
         let codemap = CodeMap::new();
         let filemap = codemap.new_filemap("".into(), SOURCE.into());
-        let tokens = read_tokens(filemap);
+        let tokens = read_tokens(filemap.clone());
 
         let wrappers = vec![
             Wrapper {
                 prefix: Chunk { position: 0, text: "<:0:>".into() },
-                postfix: Chunk { position: 1, text: "</:0:>".into() }
+                postfix: Chunk { position: 1, text: "</:0:>".into() },
+                reopen: None
             },
             Wrapper {
                 prefix: Chunk { position: 0, text: "<:1:>".into() },
-                postfix: Chunk { position: 11, text: "</:1:>".into() }
+                postfix: Chunk { position: 11, text: "</:1:>".into() },
+                reopen: None
             },
             Wrapper {
                 prefix: Chunk { position: 10, text: "<:2:>".into() },
-                postfix: Chunk { position: 11, text: "</:2:>".into() }
+                postfix: Chunk { position: 11, text: "</:2:>".into() },
+                reopen: None
             },
         ];
 
-        let result = render(&codemap, tokens, wrappers);
-        assert_eq!(result, "<:1:><:0:>fn</:0:> main() <:2:>{}</:2:></:1:>\n")
+        let result = apply_wrappers(&filemap, tokens, wrappers);
+        assert_eq!(result, "<:1:><:0:><span class=\"keyword\">fn</span></:0:> main() <:2:>{}</:2:></:1:>\n")
+    }
+
+    #[test]
+    fn test_wrappers_inside_of_token() {
+        const STRING: &'static str = "let x = \"abcdef\";\n";
+        let codemap = CodeMap::new();
+        let filemap = codemap.new_filemap("".into(), STRING.into());
+        let tokens = read_tokens(filemap.clone());
+
+        let wrappers = vec![
+            Wrapper {
+                prefix: Chunk { position: 9, text: "<:a:>".into() },
+                postfix: Chunk { position: 11, text: "</:a:>".into() },
+                reopen: None
+            },
+            Wrapper {
+                prefix: Chunk { position: 10, text: "<:b:>".into() },
+                postfix: Chunk { position: 13, text: "</:b:>".into() },
+                reopen: None
+            },
+        ];
+
+        let result = apply_wrappers(&filemap, tokens, wrappers);
+        assert_eq!(result, concat!(
+            "<span class=\"keyword\">let</span> x <span class=\"operator\">=</span> ",
+            "<span class=\"string\">\"<:a:>a<:b:>bc</:b:></:a:><:b:>de</:b:>f\"</span>;\n"
+        ));
+    }
+
+    #[test]
+    fn test_wrapper_crossing_token() {
+        const STRING: &'static str = "let x = \"abcdef\";\n";
+        let codemap = CodeMap::new();
+        let filemap = codemap.new_filemap("".into(), STRING.into());
+        let tokens = read_tokens(filemap.clone());
+
+        let wrappers = vec![
+            Wrapper {
+                prefix: Chunk { position: 4, text: "<:a:>".into() },
+                postfix: Chunk { position: 9, text: "</:a:>".into() },
+                reopen: None
+            },
+            Wrapper {
+                prefix: Chunk { position: 10, text: "<:b:>".into() },
+                postfix: Chunk { position: 100, text: "</:b:>".into() },
+                reopen: None
+            },
+        ];
+
+        let result = apply_wrappers(&filemap, tokens, wrappers);
+        assert_eq!(result, concat!(
+            "<span class=\"keyword\">let</span> <:a:>x <span class=\"operator\">=</span> ",
+            "<span class=\"string\">\"a</span></:a:><span class=\"string\">bcdef\"</span>;\n"
+        ));
+    }
+
+    #[test]
+    fn test_reopened_wrapper() {
+        let codemap = CodeMap::new();
+        let filemap = codemap.new_filemap("".into(), SOURCE.into());
+        let tokens = read_tokens(filemap.clone());
+
+        let wrappers = vec![
+            Wrapper::new(Chunk::new(0, "<:a:>".into()), Chunk::new(4, "</:a:>".into())),
+            Wrapper::new(Chunk::new(3, "<:b id:>".into()), Chunk::new(6, "</:b:>".into()))
+                .set_reopen("<:b:>".into()),
+        ];
+
+        // `b` is closed with `a` and reopened without the id
+        let result = apply_wrappers(&filemap, tokens, wrappers);
+        assert_eq!(result.matches(" id:>").count(), 1);
+        assert!(result.contains(" <:b id:>ma</:b:></:a:><:b:>in</:b:>()"), "{}", result);
+    }
+
+    #[test]
+    fn test_multibyte_source() {
+        const MULTIBYTE: &'static str = "// ёжик 🦀\nfn main() {}\n";
+        let codemap = CodeMap::new();
+        let filemap = codemap.new_filemap("".into(), MULTIBYTE.into());
+        let tokens = read_tokens(filemap.clone());
+
+        let start = MULTIBYTE.find("main").unwrap();
+        let wrappers = vec![Wrapper {
+            prefix: Chunk { position: start, text: "<:0:>".into() },
+            postfix: Chunk { position: start + 3, text: "</:0:>".into() },
+            reopen: None
+        }];
+
+        let result = apply_wrappers(&filemap, tokens, wrappers);
+        assert!(result.contains("</span> <:0:>main</:0:>()"));
+    }
+
+    #[test]
+    fn test_escaping() {
+        const ADVERSARIAL: &'static str =
+            "fn f<'a>(x: &'a u8) -> bool { let s = \"<script>alert('&')</script>\"; x << 1 && true }\n";
+        let codemap = CodeMap::new();
+        let filemap = codemap.new_filemap("".into(), ADVERSARIAL.into());
+        let tokens = read_tokens(filemap.clone());
+
+        let result = apply_wrappers(&filemap, tokens, Vec::new());
+        assert!(!result.contains("<script>"));
+        assert!(result.contains(
+            "<span class=\"string\">\"&lt;script&gt;alert('&amp;')&lt;/script&gt;\"</span>"
+        ));
+        assert!(result.contains(">&lt;&lt;</span>"));
+        assert!(result.contains(">&amp;&amp;</span>"));
+        assert!(result.contains(">-&gt;</span>"));
+        assert!(result.contains(">&lt;</span>"));
+        assert!(result.contains(">&amp;</span>"));
     }
 }
@@ -0,0 +1,281 @@
+//! Serving the pages over http instead of writing them, handy for large crates.
+//!
+//! Crates are analyzed once on start, pages are rendered when they are requested.
+//! The pages of a crate are served under its output path, e.g. `/foo/bin-foo/src/main.rs.html`
+//! for cargo projects, and directories above the crates get index pages.
+//! Requests are handled one by one, which is enough for a single reader. A client which doesn't
+//! send its request in time is dropped, so it can't block the others.
+
+use std::ffi::OsStr;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use errors::{Error, Result};
+use explorer::{Config, crate_jobs};
+use file_tree::{Directory, INDEX_PAGE, render_index};
+use index::index_crate;
+use path_extensions::PathExtensions;
+use site::Site;
+
+
+/// Analyzes the crates of `config` and serves their pages on `127.0.0.1:port` until killed.
+pub fn serve(config: &Config, port: u16) -> Result<()> {
+    // paths of the crates are relative to the root of the server
+    let config = Config { output: PathBuf::new(), ..config.clone() };
+
+    let mut indexes = Vec::new();
+    for job in try!(crate_jobs(&config)) {
+        let index = try!(index_crate(&job));
+        indexes.push((job, index));
+    }
+    let mut sites = Vec::new();
+    for &(ref job, ref index) in indexes.iter() {
+        sites.push(try!(Site::new(&config, job, index)));
+    }
+    let server = Server::new(sites);
+
+    let address = format!("127.0.0.1:{}", port);
+    let listener = try!(TcpListener::bind(&address[..]).map_err(|err| Error::Serve(address.clone(), err)));
+    log_info!("serving {} on http://{}/", config.input.display(), address);
+    for stream in listener.incoming() {
+        let result = stream.and_then(|stream| server.handle(stream));
+        if let Err(err) = result {
+            log_verbose!("request failed: {}", err);
+        }
+    }
+    Ok(())
+}
+
+
+const READ_TIMEOUT_SECS: u64 = 5;
+
+
+struct Server<'a> {
+    sites: Vec<Site<'a>>,
+    /// Directories above the crates.
+    tree: Directory
+}
+
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String
+}
+
+
+impl Response {
+    fn page(body: String) -> Response {
+        Response { status: "200 OK", content_type: "text/html", body: body }
+    }
+
+    fn error(status: &'static str, message: String) -> Response {
+        Response { status: status, content_type: "text/plain", body: message }
+    }
+}
+
+
+impl<'a> Server<'a> {
+    fn new(sites: Vec<Site<'a>>) -> Server<'a> {
+        let roots = sites.iter().map(|site| site.job().output.join(INDEX_PAGE)).collect::<Vec<_>>();
+        Server { sites: sites, tree: Directory::from_paths(&roots) }
+    }
+
+    fn handle(&self, stream: TcpStream) -> io::Result<()> {
+        try!(stream.set_read_timeout(Some(Duration::from_secs(READ_TIMEOUT_SECS))));
+        let mut reader = BufReader::new(try!(stream.try_clone()));
+        let mut request_line = String::new();
+        try!(reader.read_line(&mut request_line));
+        // headers aren't used, but some clients wait for them to be read
+        loop {
+            let mut header = String::new();
+            if try!(reader.read_line(&mut header)) == 0 || header.trim().is_empty() {
+                break;
+            }
+        }
+
+        let response = self.respond(&request_line);
+        log_debug!("{} {}", request_line.trim(), response.status);
+        let mut stream = stream;
+        try!(write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: {}; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            response.status, response.content_type, response.body.len()
+        ));
+        stream.write_all(response.body.as_bytes())
+    }
+
+    fn respond(&self, request_line: &str) -> Response {
+        let mut parts = request_line.split_whitespace();
+        let path = match (parts.next(), parts.next()) {
+            (Some("GET"), Some(target)) => match request_path(target) {
+                Some(path) => path,
+                None => return Response::error("400 Bad Request", "bad request".to_string())
+            },
+            _ => return Response::error("405 Method Not Allowed", "only GET is supported".to_string())
+        };
+
+        match self.page(&path) {
+            Ok(Some(page)) => Response::page(page),
+            Ok(None) => Response::error("404 Not Found", format!("{} not found", path.display())),
+            Err(err) => Response::error("500 Internal Server Error", err.to_string())
+        }
+    }
+
+    /// Renders the page at `path` relative to the root of the server.
+    fn page(&self, path: &Path) -> Result<Option<String>> {
+        for site in self.sites.iter() {
+            let output = &site.job().output;
+            if !path.starts_with(output) {
+                continue;
+            }
+            if let Some(relative_path) = path.relative_to(output) {
+                if let Some(page) = try!(site.render(&relative_path)) {
+                    return Ok(Some(page));
+                }
+            }
+        }
+
+        if path.file_name() == Some(OsStr::new(INDEX_PAGE)) {
+            let dir = path.parent().unwrap_or(Path::new(""));
+            if let Some(directory) = self.tree.find(dir) {
+                return Ok(Some(render_index(dir, directory)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+
+/// Page requested by the target of the request line, `None` for paths leaving the root.
+/// Directories are resolved to their index pages.
+fn request_path(target: &str) -> Option<PathBuf> {
+    let path = match percent_decode(target.split(|ch| ch == '?' || ch == '#').next().unwrap_or("")) {
+        Some(path) => path,
+        None => return None
+    };
+
+    let mut page = PathBuf::new();
+    for name in path.split('/') {
+        match name {
+            "" | "." => (),
+            ".." => return None,
+            name => page.push(name)
+        }
+    }
+    if path.ends_with('/') || page.components().count() == 0 {
+        page.push(INDEX_PAGE);
+    }
+    Some(page)
+}
+
+
+/// `src/a%20b.html` -> `src/a b.html`
+fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if i + 2 >= bytes.len() {
+                return None;
+            }
+            let hex = match String::from_utf8(bytes[i + 1..i + 3].to_vec()) {
+                Ok(hex) => hex,
+                Err(_) => return None
+            };
+            match u8::from_str_radix(&hex, 16) {
+                Ok(byte) => decoded.push(byte),
+                Err(_) => return None
+            }
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use compiler_api::{CompilerOptions, CrateType};
+    use explorer::{Config, CrateJob};
+    use index::{Index, SourceFile};
+    use navigation::{Definition, Region};
+    use site::Site;
+    use super::{Server, percent_decode, request_path};
+
+    fn fixture() -> (Config, CrateJob, Index) {
+        let filename = "/crate/src/main.rs";
+        let file = SourceFile { name: filename.to_string(), src: "fn main() {}\n".to_string(), tokens: vec![] };
+        let index = Index {
+            definitions: vec![Definition {
+                id: 1,
+                region: Region { filename: filename.to_string(), start: 3, end: 6 },
+                path: Some("main".to_string()),
+                kind: "fn"
+            }],
+            ..Index::new(vec![file])
+        };
+        let job = CrateJob {
+            name: "foo bin-foo".to_string(),
+            source_path: PathBuf::from(filename),
+            crate_type: CrateType::Executable,
+            compiler_options: CompilerOptions::default(),
+            input: PathBuf::from("/crate"),
+            output: PathBuf::from("foo/bin-foo")
+        };
+        let config = Config { template: None, ..Config::new(job.input.clone(), PathBuf::new(), PathBuf::new()) };
+        (config, job, index)
+    }
+
+    #[test]
+    fn test_page() {
+        let (config, job, index) = fixture();
+        let server = Server::new(vec![Site::new(&config, &job, &index).unwrap()]);
+        let page = |path: &str| server.page(Path::new(path)).unwrap();
+
+        let main = page("foo/bin-foo/src/main.rs.html").unwrap();
+        assert!(main.contains("id=\"def-1\""));
+        assert!(page("foo/bin-foo/refs/def-1.html").unwrap().contains("main"));
+        assert!(page("foo/bin-foo/index.html").unwrap().contains("src/index.html"));
+        assert!(page("index.html").unwrap().contains("foo/index.html"));
+        assert!(page("foo/index.html").unwrap().contains("bin-foo/index.html"));
+        assert_eq!(page("foo/bin-foo/src/other.rs.html"), None);
+        assert_eq!(page("bar/index.html"), None);
+    }
+
+    #[test]
+    fn test_respond() {
+        let (config, job, index) = fixture();
+        let server = Server::new(vec![Site::new(&config, &job, &index).unwrap()]);
+
+        let found = server.respond("GET /foo/bin-foo/src/main.rs.html HTTP/1.1\r\n");
+        assert_eq!((found.status, found.content_type), ("200 OK", "text/html"));
+        let missing = server.respond("GET /foo/missing.html HTTP/1.1\r\n");
+        assert_eq!((missing.status, missing.content_type), ("404 Not Found", "text/plain"));
+        assert_eq!(missing.body, "foo/missing.html not found");
+        let post = server.respond("POST / HTTP/1.1\r\n");
+        assert_eq!((post.status, post.content_type), ("405 Method Not Allowed", "text/plain"));
+    }
+
+    #[test]
+    fn test_request_path() {
+        assert_eq!(request_path("/"), Some(PathBuf::from("index.html")));
+        assert_eq!(request_path("/src/main.html?x=1#L10"), Some(PathBuf::from("src/main.html")));
+        assert_eq!(request_path("/foo/bin-foo/"), Some(PathBuf::from("foo/bin-foo/index.html")));
+        assert_eq!(request_path("/src/../../etc/passwd"), None);
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("a%20b.html"), Some("a b.html".to_string()));
+        assert_eq!(percent_decode("%D1%91"), Some("ё".to_string()));
+        assert_eq!(percent_decode("a%2"), None);
+        assert_eq!(percent_decode("a%zz"), None);
+    }
+}
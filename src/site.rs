@@ -0,0 +1,617 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use diagnostics::{Diagnostic, render_diagnostics};
+use errors::{Error, Result};
+use explorer::{Config, CrateJob};
+use external_links::{ExternalLinks, ITEMS_INDEX, render_items_index};
+use file_tree::{Directory, INDEX_PAGE, render_tree, render_index};
+use html::tags::{Span, A};
+use index::{Index, SourceFile};
+use log::timed;
+use navigation::{Definition, ActiveRegion, TypeHint, InactiveRegion, Target, Region};
+use output::OutputWriter;
+use path_extensions::PathExtensions;
+use references::{Reference, collect_references, line_and_snippet, render_references};
+use render::{Chunk, Wrapper, render_tokens};
+
+
+const REFS_DIR: &'static str = "refs";
+
+/// Used when no template is given.
+pub const DEFAULT_TEMPLATE: &'static str = include_str!("../static/template.html");
+
+
+/// What was written for crates.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stats {
+    pub files: usize,
+    pub definitions: usize,
+    pub links: usize,
+    /// Wrappers outside of the source, e.g. of regions with broken spans.
+    pub unapplied_wrappers: usize
+}
+
+
+impl Stats {
+    pub fn add(&mut self, other: &Stats) {
+        self.files += other.files;
+        self.definitions += other.definitions;
+        self.links += other.links;
+        self.unapplied_wrappers += other.unapplied_wrappers;
+    }
+}
+
+
+/// Writes the pages of the crate to `job.output`: the rendered files, directory indexes,
+/// references of every definition and the index of items.
+pub fn write_site(config: &Config, job: &CrateJob, index: &Index) -> Result<Stats> {
+    let site = try!(Site::new(config, job, index));
+    let mut writer = OutputWriter::new(job.output.clone());
+
+    try!(timed("write references", || site.write_references(&mut writer)));
+    try!(writer.write(Path::new(ITEMS_INDEX), &try!(site.render_items())));
+    let (links, unapplied_wrappers) = try!(timed("write pages", || site.write_pages(&mut writer)));
+
+    if config.clean {
+        for path in try!(writer.remove_stale()) {
+            log_info!("remove {:?}", path);
+        }
+    }
+    Ok(Stats {
+        files: writer.written_count(),
+        definitions: index.definitions.len(),
+        links: links,
+        unapplied_wrappers: unapplied_wrappers
+    })
+}
+
+
+/// Pages of the crate, rendered on demand. Paths of the pages are relative to `job.output`.
+pub struct Site<'a> {
+    job: &'a CrateJob,
+    index: &'a Index,
+    template: String,
+    external_links: ExternalLinks,
+    files: BTreeMap<PathBuf, &'a SourceFile>,
+    tree: Directory,
+    references: HashMap<u32, Vec<Region>>,
+    items: HashMap<&'a str, FileItems<'a>>,
+    /// Diagnostics without a location, they are shown on the page of the crate root.
+    root_diagnostics: Vec<&'a Diagnostic>
+}
+
+
+/// What is rendered into the page of a file.
+#[derive(Default)]
+struct FileItems<'a> {
+    definitions: Vec<&'a Definition>,
+    active_regions: Vec<&'a ActiveRegion>,
+    type_hints: Vec<&'a TypeHint>,
+    inactive_regions: Vec<&'a InactiveRegion>,
+    diagnostics: Vec<&'a Diagnostic>
+}
+
+
+struct RenderedFile {
+    html: String,
+    links: usize,
+    unapplied_wrappers: usize
+}
+
+
+impl<'a> Site<'a> {
+    pub fn new(config: &Config, job: &'a CrateJob, index: &'a Index) -> Result<Site<'a>> {
+        let template = match config.template {
+            Some(ref path) => try!(read_template(path)),
+            None => DEFAULT_TEMPLATE.to_string()
+        };
+        let external_links = ExternalLinks::new(
+            config.extern_explored.iter().cloned().collect(), config.extern_url.clone()
+        );
+
+        // e.g. files included with `#[path]` or `include!` from outside of the input
+        let mut files = BTreeMap::new();
+        for file in index.files.iter() {
+            match output_path(&job.input, &file.name) {
+                Ok(path) => { files.insert(path, file); },
+                Err(err) => log_info!("{}, it isn't rendered", err)
+            }
+        }
+        let tree = Directory::from_paths(files.keys());
+
+        let mut items = HashMap::new();
+        for x in index.definitions.iter() {
+            file_items(&mut items, &x.region.filename).definitions.push(x);
+        }
+        for x in index.active_regions.iter() {
+            file_items(&mut items, &x.region.filename).active_regions.push(x);
+        }
+        for x in index.type_hints.iter() {
+            file_items(&mut items, &x.region.filename).type_hints.push(x);
+        }
+        for x in index.inactive_regions.iter() {
+            file_items(&mut items, &x.region.filename).inactive_regions.push(x);
+        }
+        let mut root_diagnostics = Vec::new();
+        for x in index.diagnostics.iter() {
+            match x.region {
+                Some(ref region) => file_items(&mut items, &region.filename).diagnostics.push(x),
+                None => root_diagnostics.push(x)
+            }
+        }
+
+        Ok(Site {
+            job: job,
+            index: index,
+            template: template,
+            external_links: external_links,
+            files: files,
+            tree: tree,
+            references: collect_references(&index.active_regions),
+            items: items,
+            root_diagnostics: root_diagnostics
+        })
+    }
+
+    pub fn job(&self) -> &CrateJob {
+        self.job
+    }
+
+    /// Paths of all pages in the order they are written.
+    pub fn pages(&self) -> Vec<PathBuf> {
+        let mut pages = self.index.definitions.iter()
+            .filter(|x| self.has_page(&x.region.filename))
+            .map(|x| references_path(x.id))
+            .collect::<Vec<_>>();
+        pages.push(PathBuf::from(ITEMS_INDEX));
+        self.tree.walk(&mut |path: &Path, _: &Directory| pages.push(path.join(INDEX_PAGE)));
+        pages.extend(self.files.keys().cloned());
+        pages
+    }
+
+    /// Renders the page at `path`, `None` if there is no such page.
+    pub fn render(&self, path: &Path) -> Result<Option<String>> {
+        if let Some(file) = self.files.get(path) {
+            log_debug!("render {}", &file.name);
+            return Ok(Some(self.render_file(path, file).html));
+        }
+        if path.file_name() == Some(OsStr::new(INDEX_PAGE)) {
+            let dir = path.parent().unwrap_or(Path::new(""));
+            if let Some(directory) = self.tree.find(dir) {
+                return Ok(Some(render_index(dir, directory)));
+            }
+        }
+        if path == Path::new(ITEMS_INDEX) {
+            return self.render_items().map(Some);
+        }
+        let definition = self.index.definitions.iter()
+            .find(|x| path == &*references_path(x.id) && self.has_page(&x.region.filename));
+        match definition {
+            Some(definition) => self.render_references(definition).map(Some),
+            None => Ok(None)
+        }
+    }
+
+    /// Whether the file is rendered, i.e. it is inside of the input directory.
+    fn has_page(&self, filename: &str) -> bool {
+        output_path(&self.job.input, filename).is_ok()
+    }
+
+    fn write_references(&self, writer: &mut OutputWriter) -> Result<()> {
+        for definition in self.index.definitions.iter().filter(|x| self.has_page(&x.region.filename)) {
+            let page = try!(self.render_references(definition));
+            try!(writer.write(&references_path(definition.id), &page));
+        }
+        Ok(())
+    }
+
+    /// Writes the rendered files and directory indexes.
+    /// Returns the number of links and of wrappers which couldn't be applied.
+    fn write_pages(&self, writer: &mut OutputWriter) -> Result<(usize, usize)> {
+        let mut indexes = Vec::new();
+        self.tree.walk(&mut |path: &Path, directory: &Directory| {
+            indexes.push((path.join(INDEX_PAGE), render_index(path, directory)));
+        });
+        for (path, index) in indexes {
+            try!(writer.write(&path, &index));
+        }
+
+        let mut links = 0;
+        let mut unapplied_wrappers = 0;
+        for (relative_path, file) in self.files.iter() {
+            log_debug!("render {}", &file.name);
+            let rendered = self.render_file(relative_path, file);
+            links += rendered.links;
+            unapplied_wrappers += rendered.unapplied_wrappers;
+            try!(writer.write(relative_path, &rendered.html));
+        }
+        Ok((links, unapplied_wrappers))
+    }
+
+    fn render_items(&self) -> Result<String> {
+        let mut items = Vec::new();
+        for definition in self.index.definitions.iter().filter(|x| self.has_page(&x.region.filename)) {
+            if let Some(ref path) = definition.path {
+                items.push((path.clone(), try!(definition_href(&self.job.input, definition))));
+            }
+        }
+        Ok(render_items_index(items))
+    }
+
+    fn render_references(&self, definition: &Definition) -> Result<String> {
+        let regions = self.references.get(&definition.id).map_or(&[][..], |regions| &regions[..]);
+        let mut references = Vec::new();
+        for region in regions {
+            if let (Some(file), Ok(path)) = (self.index.file(&region.filename), output_path(&self.job.input, &region.filename)) {
+                let (line, snippet) = line_and_snippet(&file.src, region.start);
+                references.push(Reference {
+                    filename: region.filename.clone(),
+                    href: format!("../{}#L{}", path.to_string_lossy(), line),
+                    line: line,
+                    snippet: snippet
+                });
+            }
+        }
+
+        let title = definition.path.clone().unwrap_or_else(|| {
+            self.index.file(&definition.region.filename)
+                .map_or(String::new(), |file| definition.name(&file.src).to_string())
+        });
+        let definition_href = format!("../{}", try!(definition_href(&self.job.input, definition)));
+        Ok(render_references(&title, &definition_href, &references))
+    }
+
+    /// Diagnostics are underlined in the code and listed at the top of the page.
+    fn render_file(&self, relative_path: &Path, file: &SourceFile) -> RenderedFile {
+        let no_items = FileItems::default();
+        let items = self.items.get(&file.name[..]).unwrap_or(&no_items);
+
+        let mut diagnostics = items.diagnostics.iter().map(|&x| x.clone()).collect::<Vec<_>>();
+        if Path::new(&file.name) == &*self.job.source_path {
+            diagnostics.extend(self.root_diagnostics.iter().map(|&x| x.clone()));
+        }
+
+        let root = root_href(relative_path);
+        let links = items.active_regions.iter()
+            .filter_map(|x| {
+                active_region_href(x, &self.job.input, &root, &self.external_links)
+                    .map(|href| Link { region: x.region.clone(), href: href })
+            })
+            .collect::<Vec<_>>();
+        let mut wrappers = Vec::new();
+        wrappers.extend(items.definitions.iter().map(|x| x.to_wrapper()));
+        wrappers.extend(links.iter().map(|x| x.to_wrapper()));
+        wrappers.extend(items.type_hints.iter().map(|x| x.to_wrapper()));
+        wrappers.extend(items.inactive_regions.iter().map(|x| x.to_wrapper()));
+        wrappers.extend(diagnostics.iter().filter_map(|x| x.region.as_ref().map(|region| {
+            diagnostic_wrapper(x, region)
+        })));
+        let (result, unapplied_wrappers) = render_tokens(&file.src, &file.tokens, wrappers);
+        let diagnostics_html = render_diagnostics(&diagnostics, &file.src);
+        RenderedFile {
+            html: render_code(&self.template, result, &root, &render_tree(&self.tree, &root), &diagnostics_html),
+            links: links.len(),
+            unapplied_wrappers: unapplied_wrappers
+        }
+    }
+}
+
+
+fn file_items<'a, 'b>(items: &'b mut HashMap<&'a str, FileItems<'a>>, filename: &'a str) -> &'b mut FileItems<'a> {
+    items.entry(filename).or_insert_with(|| FileItems::default())
+}
+
+
+fn references_path(id: u32) -> PathBuf {
+    Path::new(REFS_DIR).join(format!("def-{}.html", id))
+}
+
+
+/// Path of the rendered file relative to the output root: `src/main.rs` -> `src/main.rs.html`.
+/// Keeping the extension of the source, the pages of sources never clash with other pages,
+/// e.g. of `index.rs` with the directory index or of `items.rs` with the items index.
+fn output_path(input: &Path, filename: &str) -> Result<PathBuf> {
+    match PathBuf::from(filename).relative_to(input) {
+        Some(path) if !path.starts_with("..") => {
+            let mut path = path.into_os_string();
+            path.push(".html");
+            Ok(PathBuf::from(path))
+        },
+        _ => Err(Error::OutsideOfInput(PathBuf::from(filename), input.to_path_buf()))
+    }
+}
+
+
+/// Relative link from the rendered file to the output root.
+fn root_href(output_path: &Path) -> String {
+    let depth = output_path.components().count().saturating_sub(1);
+    (0..depth).map(|_| "../").collect()
+}
+
+
+fn render_code<T: AsRef<str>>(template: &str, data: T, root: &str, tree: &str, diagnostics: &str) -> String {
+    fn render_lines(lines_count: usize) -> String {
+        let mut lines_buf = String::new();
+        for line in (1..lines_count + 1) {
+            lines_buf.push_str(&format!("<li id=\"L{0}\">{0}</li>", line));
+        }
+        lines_buf
+    }
+
+    let lines = render_lines(data.as_ref().lines().count());
+    let mut variables = HashMap::new();
+    variables.insert("{{code}}", data.as_ref());
+    variables.insert("{{lines}}", &lines[..]);
+    variables.insert("{{root}}", root);
+    variables.insert("{{tree}}", tree);
+    variables.insert("{{diagnostics}}", diagnostics);
+
+    render_template(template, &variables)
+}
+
+
+/// Substitutes the `{{name}}` placeholders in one pass from left to right, so the values are copied
+/// as they are, even if they contain placeholders themselves. Unknown placeholders are kept.
+fn render_template(template: &str, variables: &HashMap<&str, &str>) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let placeholder = rest.find("}}").map(|end| &rest[..end + 2]);
+        match placeholder.and_then(|placeholder| variables.get(placeholder).map(|value| (placeholder, value))) {
+            Some((placeholder, value)) => {
+                result.push_str(value);
+                rest = &rest[placeholder.len()..];
+            },
+            None => {
+                result.push_str("{{");
+                rest = &rest[2..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+
+fn read_template(path: &Path) -> Result<String> {
+    let mut file = try!(File::open(path).map_err(|err| match err.kind() {
+        io::ErrorKind::NotFound => Error::TemplateMissing(path.to_path_buf()),
+        _ => Error::io(path, err)
+    }));
+    let mut buf = String::new();
+    try!(file.read_to_string(&mut buf).map_err(|err| Error::io(path, err)));
+    Ok(buf)
+}
+
+
+trait ToWrapper {
+    fn to_wrapper(&self) -> Wrapper;
+}
+
+
+impl ToWrapper for Definition {
+    fn to_wrapper(&self) -> Wrapper {
+        let reopened = Span::new().add_class("definition").add_data("def", format!("def-{}", self.id));
+        let tag = Span::new()
+            .add_class("definition")
+            .add_id(format!("def-{}", self.id))
+            .set_name(format!("def-{}", self.id))
+            .add_data("def", format!("def-{}", self.id));
+        Wrapper::new(
+            Chunk::new(self.region.start, tag.render_open()),
+            Chunk::new(self.region.end, tag.render_close())
+        ).set_reopen(reopened.render_open())
+    }
+}
+
+
+
+impl ToWrapper for TypeHint {
+    fn to_wrapper(&self) -> Wrapper {
+        let tag = Span::new().add_class("type-hint").add_data("type", self.ty.clone());
+        Wrapper::new(
+            Chunk::new(self.region.start, tag.render_open()),
+            Chunk::new(self.region.end, tag.render_close())
+        )
+    }
+}
+
+
+impl ToWrapper for InactiveRegion {
+    fn to_wrapper(&self) -> Wrapper {
+        let tag = Span::new()
+            .add_class("cfg-inactive")
+            .set_title(format!("not compiled: cfg({}) is false", self.predicate));
+        Wrapper::new(
+            Chunk::new(self.region.start, tag.render_open()),
+            Chunk::new(self.region.end, tag.render_close())
+        )
+    }
+}
+
+
+fn diagnostic_wrapper(diagnostic: &Diagnostic, region: &Region) -> Wrapper {
+    let tag = Span::new()
+        .add_class("diagnostic")
+        .add_class(diagnostic.class())
+        .set_title(diagnostic.title());
+    Wrapper::new(
+        Chunk::new(region.start, tag.render_open()),
+        Chunk::new(region.end, tag.render_close())
+    )
+}
+
+
+struct Link {
+    region: Region,
+    href: String
+}
+
+
+impl ToWrapper for Link {
+    fn to_wrapper(&self) -> Wrapper {
+        let tag = A::new().add_class("active-region").set_href(self.href.clone());
+        Wrapper::new(
+            Chunk::new(self.region.start, tag.render_open()),
+            Chunk::new(self.region.end, tag.render_close())
+        )
+    }
+}
+
+
+/// Link from the rendered file, `root` is the link from it to the output root.
+fn active_region_href(active_region: &ActiveRegion, input: &Path, root: &str, external_links: &ExternalLinks) -> Option<String> {
+    match active_region.target {
+        Target::Definition(ref filename, id) => def_href(input, filename, id).ok().map(|href| format!("{}{}", root, href)),
+        Target::External(ref item) => external_links.resolve(item)
+    }
+}
+
+
+/// Link to the definition relative to the output root.
+fn definition_href(input: &Path, definition: &Definition) -> Result<String> {
+    def_href(input, &definition.region.filename, definition.id)
+}
+
+
+fn def_href(input: &Path, filename: &str, id: u32) -> Result<String> {
+    let path = try!(output_path(input, filename));
+    Ok(format!("{}#def-{}", path.to_string_lossy(), id))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use compiler_api::{CompilerOptions, CrateType};
+    use explorer::{Config, CrateJob};
+    use index::{Index, SourceFile};
+    use navigation::{ActiveRegion, Definition, Region, Target};
+    use super::{Site, render_code};
+
+    fn region(filename: &str, start: usize, end: usize) -> Region {
+        Region { filename: filename.to_string(), start: start, end: end }
+    }
+
+    fn source_file(name: &str, src: &str) -> SourceFile {
+        SourceFile { name: name.to_string(), src: src.to_string(), tokens: vec![] }
+    }
+
+    fn job() -> (Config, CrateJob) {
+        let job = CrateJob {
+            name: "main".to_string(),
+            source_path: PathBuf::from("/crate/src/main.rs"),
+            crate_type: CrateType::Executable,
+            compiler_options: CompilerOptions::default(),
+            input: PathBuf::from("/crate"),
+            output: PathBuf::new()
+        };
+        let config = Config { template: None, ..Config::new(job.input.clone(), job.output.clone(), PathBuf::new()) };
+        (config, job)
+    }
+
+    #[test]
+    fn test_file_outside_of_input() {
+        let files = vec![
+            source_file("/crate/src/main.rs", "include!(\"../../gen/consts.rs\");\nfn main() { X; }\n"),
+            source_file("/gen/consts.rs", "const X: u8 = 1;\n")
+        ];
+        let index = Index {
+            definitions: vec![
+                Definition { id: 1, region: region("/crate/src/main.rs", 36, 39), path: Some("main".into()), kind: "fn" },
+                Definition { id: 2, region: region("/gen/consts.rs", 6, 6), path: Some("X".into()), kind: "const" }
+            ],
+            active_regions: vec![
+                ActiveRegion { target: Target::Definition("/gen/consts.rs".into(), 2), region: region("/crate/src/main.rs", 45, 45) }
+            ],
+            ..Index::new(files)
+        };
+        let (config, job) = job();
+        let site = Site::new(&config, &job, &index).unwrap();
+        assert_eq!(site.pages(), vec![
+            PathBuf::from("refs/def-1.html"),
+            PathBuf::from("items.html"),
+            PathBuf::from("index.html"),
+            PathBuf::from("src/index.html"),
+            PathBuf::from("src/main.rs.html")
+        ]);
+        assert_eq!(site.render(&PathBuf::from("refs/def-2.html")).unwrap(), None);
+
+        let page = site.render(&PathBuf::from("src/main.rs.html")).unwrap().unwrap();
+        assert!(!page.contains("consts.rs.html"));
+    }
+
+    #[test]
+    fn test_links_between_directories() {
+        let files = vec![
+            source_file("/crate/src/main.rs", "mod point;\nfn main() { point::origin(); }\n"),
+            source_file("/crate/src/geometry/point.rs", "pub fn origin() { ::main() }\n")
+        ];
+        let index = Index {
+            definitions: vec![
+                Definition { id: 1, region: region("/crate/src/main.rs", 14, 17), path: Some("main".into()), kind: "fn" },
+                Definition {
+                    id: 2, region: region("/crate/src/geometry/point.rs", 7, 12), path: Some("point::origin".into()), kind: "fn"
+                }
+            ],
+            active_regions: vec![
+                ActiveRegion {
+                    target: Target::Definition("/crate/src/geometry/point.rs".into(), 2),
+                    region: region("/crate/src/main.rs", 30, 35)
+                },
+                ActiveRegion {
+                    target: Target::Definition("/crate/src/main.rs".into(), 1),
+                    region: region("/crate/src/geometry/point.rs", 20, 23)
+                }
+            ],
+            ..Index::new(files)
+        };
+        let (config, job) = job();
+        let site = Site::new(&config, &job, &index).unwrap();
+
+        let main = site.render(&PathBuf::from("src/main.rs.html")).unwrap().unwrap();
+        assert!(main.contains("\"../src/geometry/point.rs.html#def-2\""));
+        let point = site.render(&PathBuf::from("src/geometry/point.rs.html")).unwrap().unwrap();
+        assert!(point.contains("\"../../src/main.rs.html#def-1\""));
+    }
+
+    #[test]
+    fn test_pages_do_not_clash() {
+        let files = vec![
+            source_file("/crate/index.rs", "fn index() {}\n"),
+            source_file("/crate/items.rs", ""),
+            source_file("/crate/refs/def-1.rs", "")
+        ];
+        let index = Index {
+            definitions: vec![Definition { id: 1, region: region("/crate/index.rs", 3, 7), path: Some("index".into()), kind: "fn" }],
+            ..Index::new(files)
+        };
+        let (config, job) = job();
+        let site = Site::new(&config, &job, &index).unwrap();
+        let pages = site.pages();
+        let mut unique = pages.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), pages.len());
+        assert!(site.render(Path::new("index.rs.html")).unwrap().unwrap().contains("def-1"));
+        assert!(site.render(Path::new("index.html")).unwrap().unwrap().contains("index.rs.html"));
+    }
+
+    #[test]
+    fn test_render_code() {
+        let template = "<ul>{{lines}}</ul><pre>{{code}}</pre>{{diagnostics}}<a href=\"{{root}}\">{{unknown}}";
+        let code = "let s = \"{{root}} {{diagnostics}}\";";
+        assert_eq!(
+            render_code(template, code, "../", "", "<p>{{code}}</p>"),
+            concat!(
+                "<ul><li id=\"L1\">1</li></ul><pre>let s = \"{{root}} {{diagnostics}}\";</pre>",
+                "<p>{{code}}</p><a href=\"../\">{{unknown}}"
+            )
+        );
+    }
+}
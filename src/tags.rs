@@ -0,0 +1,214 @@
+//! Tags files for editors: `tags` in the extended ctags format for Vim and `TAGS` for Emacs.
+//!
+//! Tags are made of all items of the crate, whether it refers to them or not; local bindings are skipped.
+//! File names are absolute, so the files can be used from any directory.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
+use errors::Result;
+use explorer::CrateJob;
+use index::Index;
+use output::OutputWriter;
+use references::line_and_snippet;
+use site::Stats;
+
+
+pub const CTAGS_FILE: &'static str = "tags";
+pub const ETAGS_FILE: &'static str = "TAGS";
+
+
+/// Definition as it goes to the tags file.
+#[derive(Debug, PartialEq)]
+pub struct Tag {
+    pub name: String,
+    pub filename: String,
+    pub line: usize, // starting from 1
+    pub line_start: usize, // byte offset of the line from the start of the file
+    pub line_text: String, // text of the line up to the end of the name
+    pub kind: &'static str
+}
+
+
+pub fn write_ctags(job: &CrateJob, index: &Index) -> Result<Stats> {
+    write_tags(job, index, CTAGS_FILE, render_ctags)
+}
+
+
+pub fn write_etags(job: &CrateJob, index: &Index) -> Result<Stats> {
+    write_tags(job, index, ETAGS_FILE, render_etags)
+}
+
+
+fn write_tags(job: &CrateJob, index: &Index, file: &str, render: fn(&[Tag]) -> String) -> Result<Stats> {
+    let base = env::current_dir().unwrap_or(PathBuf::from("."));
+    let tags = collect_tags(&base, index);
+    let mut writer = OutputWriter::new(job.output.clone());
+    try!(writer.write(Path::new(file), &render(&tags)));
+    Ok(Stats {
+        files: writer.written_count(),
+        definitions: tags.len(),
+        links: 0,
+        unapplied_wrappers: 0
+    })
+}
+
+
+/// Tags sorted by name. Relative file names are resolved against `base`.
+pub fn collect_tags(base: &Path, index: &Index) -> Vec<Tag> {
+    let mut tags = Vec::new();
+    for definition in index.definitions.iter() {
+        if ctags_kind(definition.kind).is_none() {
+            continue;
+        }
+        let file = match index.file(&definition.region.filename) {
+            Some(file) => file,
+            None => continue
+        };
+        let name = definition.name(&file.src);
+        let region = &definition.region;
+        if name.is_empty() || region.start > region.end || region.end >= file.src.len() {
+            continue;
+        }
+
+        // the region is the name of the definition
+        let start = region.start;
+        let line_start = file.src[..start].rfind('\n').map_or(0, |index| index + 1);
+        let line_end = file.src[start..].find('\n').map_or(file.src.len(), |index| start + index);
+        let name_end = if region.end < line_end { region.end + 1 } else { line_end };
+
+        tags.push(Tag {
+            name: name.to_string(),
+            filename: base.join(&file.name).to_string_lossy().into_owned(),
+            line: line_and_snippet(&file.src, start).0,
+            line_start: line_start,
+            line_text: file.src[line_start..name_end].to_string(),
+            kind: definition.kind
+        });
+    }
+    tags.sort_by(|a, b| (&a.name, &a.filename, a.line).cmp(&(&b.name, &b.filename, b.line)));
+    tags
+}
+
+
+/// One letter kinds of universal-ctags for Rust.
+fn ctags_kind(kind: &str) -> Option<&'static str> {
+    match kind {
+        "mod" => Some("n"),
+        "struct" => Some("s"),
+        "trait" => Some("i"),
+        "fn" => Some("f"),
+        "enum" => Some("g"),
+        "type" => Some("t"),
+        "static" => Some("v"),
+        "field" => Some("m"),
+        "variant" => Some("e"),
+        "method" => Some("P"),
+        "const" => Some("C"),
+        _ => None
+    }
+}
+
+
+pub fn render_ctags(tags: &[Tag]) -> String {
+    let mut buf = String::new();
+    buf.push_str("!_TAG_FILE_FORMAT\t2\t/extended format/\n");
+    buf.push_str("!_TAG_FILE_SORTED\t1\t/0=unsorted, 1=sorted, 2=foldcase/\n");
+    buf.push_str("!_TAG_PROGRAM_NAME\tcode_explorer\t//\n");
+    for tag in tags {
+        buf.push_str(&format!(
+            "{}\t{}\t{};\"\t{}\tline:{}\n",
+            tag.name, tag.filename, tag.line, ctags_kind(tag.kind).unwrap_or("v"), tag.line
+        ));
+    }
+    buf
+}
+
+
+pub fn render_etags(tags: &[Tag]) -> String {
+    let mut by_file = BTreeMap::new();
+    for tag in tags {
+        by_file.entry(&tag.filename[..]).or_insert_with(|| Vec::new()).push(tag);
+    }
+
+    let mut buf = String::new();
+    for (filename, mut tags) in by_file {
+        tags.sort_by(|a, b| a.line.cmp(&b.line));
+        let mut section = String::new();
+        for tag in tags {
+            section.push_str(&format!(
+                "{}\x7f{}\x01{},{}\n", tag.line_text, tag.name, tag.line, tag.line_start
+            ));
+        }
+        buf.push_str(&format!("\x0c\n{},{}\n{}", filename, section.len(), section));
+    }
+    buf
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use highlight::HighlightedToken;
+    use index::{Index, SourceFile};
+    use navigation::{Definition, Region};
+    use super::{Tag, collect_tags, render_ctags, render_etags};
+
+    const SOURCE: &'static str = "mod shapes;\n\npub struct Point { x: u8 }\n\nfn main() { let p = 1; }\n";
+
+    fn definition(id: u32, text: &str, path: Option<&str>, kind: &'static str) -> Definition {
+        let start = SOURCE.find(text).unwrap();
+        Definition {
+            id: id,
+            region: Region { filename: "src/main.rs".to_string(), start: start, end: start + text.len() - 1 },
+            path: path.map(|path| path.to_string()),
+            kind: kind
+        }
+    }
+
+    fn tags() -> Vec<Tag> {
+        let file = SourceFile {
+            name: "src/main.rs".to_string(),
+            src: SOURCE.to_string(),
+            tokens: vec![HighlightedToken { start: 0, end: 2, class: Some("keyword") }]
+        };
+        let index = Index {
+            definitions: vec![
+                definition(1, "main", Some("main"), "fn"),
+                definition(2, "Point", Some("Point"), "struct"),
+                definition(3, "x", None, "field"),
+                definition(4, "p = 1", None, "local")
+            ],
+            ..Index::new(vec![file])
+        };
+        collect_tags(Path::new("/project"), &index)
+    }
+
+    #[test]
+    fn test_collect_tags() {
+        let tags = tags();
+        assert_eq!(tags.iter().map(|tag| &tag.name[..]).collect::<Vec<_>>(), vec!["Point", "main", "x"]);
+        assert_eq!(tags[0].line, 3);
+        assert_eq!(tags[0].line_start, 13);
+        assert_eq!(tags[0].line_text, "pub struct Point");
+        assert_eq!(tags[0].filename, "/project/src/main.rs");
+    }
+
+    #[test]
+    fn test_render_ctags() {
+        let ctags = render_ctags(&tags());
+        assert!(ctags.starts_with("!_TAG_FILE_FORMAT\t2\t/extended format/\n"));
+        assert!(ctags.contains("Point\t/project/src/main.rs\t3;\"\ts\tline:3\n"));
+        assert!(ctags.contains("main\t/project/src/main.rs\t5;\"\tf\tline:5\n"));
+        assert!(ctags.contains("x\t/project/src/main.rs\t3;\"\tm\tline:3\n"));
+    }
+
+    #[test]
+    fn test_render_etags() {
+        let section = "pub struct Point\x7fPoint\x013,13\npub struct Point { x\x7fx\x013,13\nfn main\x7fmain\x015,41\n";
+        assert_eq!(
+            render_etags(&tags()),
+            format!("\x0c\n/project/src/main.rs,{}\n{}", section.len(), section)
+        );
+    }
+}
@@ -0,0 +1,155 @@
+//! Watch mode: the crate is explored again whenever its sources, manifests or the template change.
+//!
+//! Changes are found by polling, files are compared by hashes of their contents,
+//! so touching a file without changing it doesn't trigger a run.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::hash::{Hasher, SipHasher};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::thread;
+use explorer::{Config, CrateJob};
+use index::{Index, source_files};
+use manifest::MANIFEST;
+
+
+const POLL_INTERVAL_MS: u32 = 500;
+
+
+/// Hashes of the watched files, missing files have no entry.
+#[derive(Debug, PartialEq)]
+pub struct Snapshot(BTreeMap<PathBuf, u64>);
+
+
+impl Snapshot {
+    /// Rust files of the input directory, its manifest, the template and `explored`,
+    /// i.e. the files read by the last run, see `explored_files`.
+    pub fn take(config: &Config, explored: &[PathBuf]) -> Snapshot {
+        let mut paths = source_files(&config.input);
+        paths.push(config.input.join(MANIFEST));
+        paths.extend(config.template.iter().cloned());
+        paths.extend(explored.iter().cloned());
+
+        let mut hashes = BTreeMap::new();
+        for path in paths {
+            if let Some(hash) = content_hash(&path) {
+                hashes.insert(path, hash);
+            }
+        }
+        Snapshot(hashes)
+    }
+
+    /// Files which were added, removed or changed since `earlier`.
+    pub fn changed(&self, earlier: &Snapshot) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        for (path, hash) in self.0.iter() {
+            if earlier.0.get(path) != Some(hash) {
+                changed.push(path.clone());
+            }
+        }
+        for path in earlier.0.keys() {
+            if !self.0.contains_key(path) {
+                changed.push(path.clone());
+            }
+        }
+        changed
+    }
+}
+
+
+/// Calls `explore` every time the watched files change, never returns. `explore` returns the files
+/// it has read, they are watched until the next run. The caller is expected to explore once before
+/// watching and to pass the files of that run.
+pub fn watch<F: FnMut() -> Vec<PathBuf>>(config: &Config, explored: Vec<PathBuf>, mut explore: F) {
+    let mut explored = explored;
+    let mut snapshot = Snapshot::take(config, &explored);
+    log_info!("watching {} for changes", config.input.display());
+    loop {
+        thread::sleep_ms(POLL_INTERVAL_MS);
+        let current = Snapshot::take(config, &explored);
+        let changed = current.changed(&snapshot);
+        if changed.is_empty() {
+            continue;
+        }
+        for path in changed.iter() {
+            log_verbose!("changed {}", path.display());
+        }
+        log_info!("{} files changed, exploring again", changed.len());
+        explored = explore();
+        // files read for the first time are known only after the run
+        snapshot = Snapshot::take(config, &explored);
+    }
+}
+
+
+/// Files the crate was explored from: its sources, including the ones outside of the input
+/// directory, and the manifest of its package.
+pub fn explored_files(job: &CrateJob, index: &Index) -> Vec<PathBuf> {
+    let mut files = index.files.iter().map(|file| PathBuf::from(&file.name)).collect::<Vec<_>>();
+    files.push(job.input.join(MANIFEST));
+    files
+}
+
+
+fn content_hash(path: &Path) -> Option<u64> {
+    let mut buf = Vec::new();
+    match File::open(path).and_then(|mut file| file.read_to_end(&mut buf)) {
+        Ok(_) => {
+            let mut hasher = SipHasher::new();
+            hasher.write(&buf);
+            Some(hasher.finish())
+        },
+        Err(_) => None
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::path::PathBuf;
+    use explorer::Config;
+    use fixture::{temp_dir, write};
+    use super::Snapshot;
+
+    fn snapshot(hashes: &[(&str, u64)]) -> Snapshot {
+        Snapshot(hashes.iter().map(|&(path, hash)| (PathBuf::from(path), hash)).collect::<BTreeMap<_, _>>())
+    }
+
+    #[test]
+    fn test_changed() {
+        let earlier = snapshot(&[("src/lib.rs", 1), ("src/foo.rs", 2), ("src/bar.rs", 3)]);
+        let current = snapshot(&[("src/lib.rs", 1), ("src/foo.rs", 4), ("src/baz.rs", 5)]);
+        assert_eq!(
+            current.changed(&earlier),
+            vec![PathBuf::from("src/baz.rs"), PathBuf::from("src/foo.rs"), PathBuf::from("src/bar.rs")]
+        );
+        assert!(current.changed(&current).is_empty());
+    }
+
+    #[test]
+    fn test_take() {
+        let root = temp_dir("snapshot");
+        let input = root.join("crate");
+        write(&input.join("Cargo.toml"), "[package]\nname = \"x\"\n");
+        write(&input.join("src/lib.rs"), "include!(\"../../gen/consts.rs\");\n");
+        write(&root.join("gen/consts.rs"), "const X: u8 = 1;\n");
+        write(&root.join("template.html"), "{{code}}");
+        let config = Config::new(input.clone(), root.join("out"), root.join("template.html"));
+        let explored = vec![input.join("src/lib.rs"), root.join("gen/consts.rs"), input.join("Cargo.toml")];
+
+        let earlier = Snapshot::take(&config, &explored);
+        assert_eq!(earlier.0.keys().cloned().collect::<Vec<_>>(), vec![
+            input.join("Cargo.toml"), input.join("src/lib.rs"), root.join("gen/consts.rs"), root.join("template.html")
+        ]);
+
+        write(&input.join("Cargo.toml"), "[package]\nname = \"y\"\n");
+        write(&root.join("gen/consts.rs"), "const X: u8 = 2;\n");
+        write(&input.join("src/lib.rs"), "include!(\"../../gen/consts.rs\");\n");
+        let current = Snapshot::take(&config, &explored);
+        assert_eq!(current.changed(&earlier), vec![input.join("Cargo.toml"), root.join("gen/consts.rs")]);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,45 @@
+extern crate code_explorer;
+
+
+use std::fs;
+use std::path::Path;
+use code_explorer::{Config, Target, crate_jobs, index_crate, write_index};
+use fixture::{read, temp_files};
+
+
+#[path = "../src/fixture.rs"]
+mod fixture;
+
+
+const MAIN: &'static str = "mod point;\n\nfn main() {\n    let p = point::origin();\n    println!(\"{}\", p.x);\n}\n";
+const POINT: &'static str = "pub struct Point { pub x: i32 }\n\npub fn origin() -> Point { Point { x: 0 } }\n";
+
+
+#[test]
+fn test_explore() {
+    let input = temp_files("explore", &[("main.rs", MAIN), ("point.rs", POINT)]);
+    let output = input.join("out");
+    let template = Path::new(env!("CARGO_MANIFEST_DIR")).join("static/template.html");
+
+    let config = Config::new(input.clone(), output.clone(), template);
+    let jobs = crate_jobs(&config).unwrap();
+    assert_eq!(jobs.len(), 1);
+    for job in jobs {
+        let index = index_crate(&job).unwrap();
+        assert!(index.failure.is_none());
+
+        let origin = index.definitions.iter().find(|x| x.path == Some("point::origin".to_string())).unwrap();
+        assert!(index.active_regions.iter().any(|x| match x.target {
+            Target::Definition(ref filename, id) => id == origin.id && filename.ends_with("point.rs"),
+            Target::External(_) => false
+        }));
+
+        let stats = write_index(&config, &job, &index).unwrap();
+        assert!(stats.files > 0);
+    }
+
+    assert!(read(&output.join("main.rs.html")).contains("point.rs.html#def-"));
+    assert!(read(&output.join("point.rs.html")).contains("Point"));
+    assert!(output.join("index.html").exists());
+    fs::remove_dir_all(&input).unwrap();
+}